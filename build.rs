@@ -0,0 +1,90 @@
+// Generates the C header for `src/ffi.rs` into OUT_DIR and warns when the
+// shipped `include/toptree.h` differs. Set TOPTREE_WRITE_HEADER=1 to update it.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn c_type(ty: &str) -> String {
+    match ty.trim() {
+        "u64" => "uint64_t".to_string(),
+        "u32" => "uint32_t".to_string(),
+        "i32" => "int32_t".to_string(),
+        "*mut ToptreeForest" => "toptree_forest *".to_string(),
+        "*mut u64" => "uint64_t *".to_string(),
+        "*mut i32" => "int32_t *".to_string(),
+        ty => panic!("no C type for `{}`", ty),
+    }
+}
+
+fn header(src: &str) -> String {
+    let mut out = String::from(
+        "/* Generated from src/ffi.rs by build.rs. Do not edit. */\n\
+         #ifndef TOPTREE_H\n#define TOPTREE_H\n\n#include <stdint.h>\n\n\
+         #ifdef __cplusplus\nextern \"C\" {\n#endif\n\n\
+         typedef struct toptree_forest toptree_forest;\n",
+    );
+    let mut doc = Vec::new();
+    let mut group = "";
+    for line in src.lines() {
+        let line = line.trim();
+        if let Some(d) = line.strip_prefix("///") {
+            let d = d.trim();
+            doc.push(if d == "# Safety" { "Safety:".to_string() } else { d.to_string() });
+        }
+        else if let Some(c) = line.strip_prefix("pub const ") {
+            // `NAME: TYPE = VALUE;`, status codes and cluster kinds are separate groups
+            let name = c.split(':').next().unwrap().trim();
+            let value = c.split('=').nth(1).unwrap().trim().trim_end_matches(';');
+            let g = if name.starts_with("TOPTREE_CLUSTER_") { "cluster" } else { "status" };
+            if g != group {
+                out.push('\n');
+                group = g;
+            }
+            out.push_str(&format!("#define {} {}\n", name, value));
+            doc.clear();
+        }
+        else if line.contains("extern \"C\" fn ") {
+            let sig = line.split("fn ").nth(1).unwrap().trim_end_matches('{').trim();
+            let name = &sig[..sig.find('(').unwrap()];
+            let args = &sig[sig.find('(').unwrap() + 1..sig.rfind(')').unwrap()];
+            let ret = sig.split("->").nth(1).map_or("void".to_string(), c_type);
+            let args: Vec<String> = args.split(", ").filter(|a| !a.is_empty()).map(|a| {
+                let mut it = a.splitn(2, ':');
+                let (arg, ty) = (it.next().unwrap().trim(), it.next().unwrap());
+                let ty = c_type(ty);
+                if ty.ends_with('*') { format!("{}{}", ty, arg) } else { format!("{} {}", ty, arg) }
+            }).collect();
+            out.push_str("\n/*\n");
+            for d in doc.drain(..) {
+                out.push_str(if d.is_empty() { " *\n".to_string() } else { format!(" * {}\n", d) }.as_str());
+            }
+            out.push_str(" */\n");
+            let ret = if ret.ends_with('*') { ret } else { format!("{} ", ret) };
+            out.push_str(&format!("{}{}({});\n", ret, name, if args.is_empty() { "void".to_string() } else { args.join(", ") }));
+        }
+        else if !line.starts_with("#[") {
+            doc.clear();
+        }
+    }
+    out.push_str("\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n");
+    out
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=include/toptree.h");
+    println!("cargo:rerun-if-env-changed=TOPTREE_WRITE_HEADER");
+    let src = fs::read_to_string("src/ffi.rs").expect("cannot read src/ffi.rs");
+    let generated = header(&src);
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("toptree.h");
+    fs::write(&out, &generated).unwrap();
+    let shipped = Path::new("include/toptree.h");
+    if env::var("TOPTREE_WRITE_HEADER").is_ok() {
+        fs::create_dir_all("include").unwrap();
+        fs::write(shipped, &generated).unwrap();
+    }
+    else if fs::read_to_string(shipped).ok().as_deref() != Some(generated.as_str()) {
+        println!("cargo:warning=include/toptree.h is out of date, rebuild with TOPTREE_WRITE_HEADER=1");
+    }
+}
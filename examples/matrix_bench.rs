@@ -0,0 +1,138 @@
+// Path products of k x k matrices under random cut/link, timed with std::time.
+//
+//     cargo run --release --example matrix_bench [N] [OPS] [K]
+
+use std::time::Instant;
+use toptree::node::*;
+use toptree::link::*;
+use toptree::cut::*;
+use toptree::path_query::*;
+use toptree::expose::*;
+
+const MOD: u64 = 998_244_353;
+
+#[derive(Debug)]
+struct Mat {
+    k: usize,
+    a: Vec<u64>,
+}
+
+// `clone_from` reuses the buffer, which `#[derive(Clone)]` would not
+impl Clone for Mat {
+    fn clone(&self) -> Self {
+        Mat { k: self.k, a: self.a.clone() }
+    }
+    fn clone_from(&mut self, other: &Self) {
+        self.k = other.k;
+        self.a.clone_from(&other.a);
+    }
+}
+
+impl Mat {
+    fn random(k: usize, rng: &mut Rng) -> Self {
+        Mat { k, a: (0..k * k).map(|_| rng.next() % MOD).collect() }
+    }
+    // `out` keeps its buffer
+    fn mul(x: &Mat, y: &Mat, out: &mut Mat) {
+        // the identity cluster does not know `k`
+        if x.k == 0 { return out.clone_from(y) }
+        if y.k == 0 { return out.clone_from(x) }
+        let k = x.k;
+        out.k = k;
+        out.a.clear();
+        out.a.resize(k * k, 0);
+        for i in 0..k {
+            for l in 0..k {
+                let xil = x.a[i * k + l];
+                for j in 0..k {
+                    out.a[i * k + j] = (out.a[i * k + j] + xil * y.a[l * k + j]) % MOD;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PathMatrix {
+    fwd: Mat,
+    bwd: Mat,
+}
+
+impl Clone for PathMatrix {
+    fn clone(&self) -> Self {
+        PathMatrix { fwd: self.fwd.clone(), bwd: self.bwd.clone() }
+    }
+    fn clone_from(&mut self, other: &Self) {
+        self.fwd.clone_from(&other.fwd);
+        self.bwd.clone_from(&other.bwd);
+    }
+}
+
+impl Cluster for PathMatrix {
+    type V = ();
+    fn identity() -> Self {
+        PathMatrix { fwd: Mat { k: 0, a: Vec::new() }, bwd: Mat { k: 0, a: Vec::new() } }
+    }
+    fn compress(a: &Self, b: &Self, _: &(), _: &(), _: &(), out: &mut Self) {
+        Mat::mul(&a.fwd, &b.fwd, &mut out.fwd);
+        Mat::mul(&b.bwd, &a.bwd, &mut out.bwd);
+    }
+    fn rake(a: &Self, _: &Self, _: &(), _: &(), _: &(), out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.fwd, &mut self.bwd);
+    }
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn main() {
+    let args: Vec<usize> = std::env::args().skip(1).map(|a| a.parse().expect("arguments are N OPS K")).collect();
+    let n = args.first().copied().unwrap_or(2000);
+    let ops = args.get(1).copied().unwrap_or(20_000);
+    let k = args.get(2).copied().unwrap_or(4);
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    let start = Instant::now();
+    let v: Vec<Vertex<PathMatrix>> = (0..n).map(|_| Vertex::new(())).collect();
+    let mut edges = Vec::new();
+    for i in 1..n {
+        let p = rng.below(i);
+        let m = Mat::random(k, &mut rng);
+        link(v[p], v[i], PathMatrix { fwd: m.clone(), bwd: m });
+        edges.push((p, i));
+    }
+    let built = start.elapsed();
+
+    let start = Instant::now();
+    let mut check = 0u64;
+    for _ in 0..ops {
+        let i = rng.below(edges.len());
+        let (a, b) = edges[i];
+        cut(v[a], v[b]);
+        let (x, y) = (rng.below(n), rng.below(n));
+        edges[i] = if connected(v[x], v[y]) { (a, b) } else { (x, y) };
+        let m = Mat::random(k, &mut rng);
+        link(v[edges[i].0], v[edges[i].1], PathMatrix { fwd: m.clone(), bwd: m });
+        let (x, y) = (rng.below(n), rng.below(n));
+        if x != y {
+            let p = path_query(v[x], v[y]).fwd;
+            check = (check + p.a.iter().sum::<u64>()) % MOD;
+        }
+    }
+    let ran = start.elapsed();
+    println!("n = {}, ops = {}, k = {}", n, ops, k);
+    println!("build {:?}, cut + link + path {:?} ({:?} per op)", built, ran, ran / ops.max(1) as u32);
+    println!("checksum {}", check);
+}
@@ -0,0 +1,134 @@
+/* Generated from src/ffi.rs by build.rs. Do not edit. */
+#ifndef TOPTREE_H
+#define TOPTREE_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct toptree_forest toptree_forest;
+
+#define TOPTREE_OK 0
+#define TOPTREE_NULL_POINTER 1
+#define TOPTREE_UNKNOWN_VERTEX 2
+#define TOPTREE_SAME_VERTEX 3
+#define TOPTREE_ALREADY_CONNECTED 4
+#define TOPTREE_NOT_CONNECTED 5
+#define TOPTREE_NO_SUCH_EDGE 6
+#define TOPTREE_NOT_ISOLATED 7
+#define TOPTREE_UNSUPPORTED 8
+#define TOPTREE_OUT_OF_RANGE 9
+#define TOPTREE_INTERNAL_ERROR 10
+#define TOPTREE_EMPTY 11
+
+#define TOPTREE_CLUSTER_LENGTH 0
+#define TOPTREE_CLUSTER_DIAMETER 1
+#define TOPTREE_CLUSTER_CENTER 2
+#define TOPTREE_CLUSTER_MEDIAN 3
+
+/*
+ * Creates an empty forest of the given `TOPTREE_CLUSTER_*` kind. Returns NULL for an unknown kind.
+ * Weights, vertex values and answers are `u64`; sums saturate at `UINT64_MAX`.
+ * Once a call returns `TOPTREE_INTERNAL_ERROR` the forest may be broken, and
+ * every later call on it returns `TOPTREE_INTERNAL_ERROR` until it is freed.
+ */
+toptree_forest *toptree_forest_new(uint32_t kind);
+
+/*
+ * Frees a forest and all of its vertices. NULL is ignored.
+ *
+ * Safety:
+ * `forest` must be NULL or come from `toptree_forest_new` and not be used afterwards.
+ */
+void toptree_forest_free(toptree_forest *forest);
+
+/*
+ * Adds a vertex with the given value and writes its id to `out_id`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and `out_id` must be writable.
+ */
+int32_t toptree_add_vertex(toptree_forest *forest, uint64_t value, uint64_t *out_id);
+
+/*
+ * Adds the edge `u`-`v` with weight `w`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new`.
+ */
+int32_t toptree_link(toptree_forest *forest, uint64_t u, uint64_t v, uint64_t w);
+
+/*
+ * Removes the edge `u`-`v`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new`.
+ */
+int32_t toptree_cut(toptree_forest *forest, uint64_t u, uint64_t v);
+
+/*
+ * Changes the weight of the edge `u`-`v` to `w`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new`.
+ */
+int32_t toptree_set_edge(toptree_forest *forest, uint64_t u, uint64_t v, uint64_t w);
+
+/*
+ * Changes the value of vertex `v`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new`.
+ */
+int32_t toptree_set_vertex(toptree_forest *forest, uint64_t v, uint64_t value);
+
+/*
+ * Writes 1 to `out` if `u` and `v` are in the same tree, 0 otherwise.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and `out` must be writable.
+ */
+int32_t toptree_connected(toptree_forest *forest, uint64_t u, uint64_t v, int32_t *out);
+
+/*
+ * Writes the sum of the edge weights on the path `u`..`v` to `out_sum`.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and `out_sum` must be writable.
+ */
+int32_t toptree_path_sum(toptree_forest *forest, uint64_t u, uint64_t v, uint64_t *out_sum);
+
+/*
+ * Writes the endpoints and the length of a diameter of the tree containing `v`.
+ * Needs a `TOPTREE_CLUSTER_DIAMETER` forest.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+ */
+int32_t toptree_diameter(toptree_forest *forest, uint64_t v, uint64_t *out_u, uint64_t *out_v, uint64_t *out_length);
+
+/*
+ * Writes a center of the tree containing `v` and its radius.
+ * Needs a `TOPTREE_CLUSTER_CENTER` forest.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+ */
+int32_t toptree_center(toptree_forest *forest, uint64_t v, uint64_t *out_center, uint64_t *out_radius);
+
+/*
+ * Writes the vertex minimizing the sum of value * distance over the tree containing `v`, and that sum.
+ * Needs a `TOPTREE_CLUSTER_MEDIAN` forest.
+ *
+ * Safety:
+ * `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+ */
+int32_t toptree_median(toptree_forest *forest, uint64_t v, uint64_t *out_median, uint64_t *out_cost);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
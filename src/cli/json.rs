@@ -0,0 +1,436 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+use toptree::forest::*;
+use super::*;
+
+/// A JSON value. Numbers keep their source text so integers of any size
+/// survive a round trip; they are converted when a field is read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json { Json::Number(n.to_string()) }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json { Json::Bool(b) }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json { Json::String(s.to_string()) }
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write_str(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// deeper arrays and objects are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn err<T>(&self, msg: &str) -> Result<T, String> {
+        Err(format!("{} at byte {}", msg, self.pos))
+    }
+    fn peek(&self) -> Option<u8> {
+        self.s.get(self.pos).cloned()
+    }
+    fn skip_ws(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        }
+        else { self.err(&format!("expected `{}`", c as char)) }
+    }
+    fn keyword(&mut self, word: &str, val: Json) -> Result<Json, String> {
+        if self.s[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(val)
+        }
+        else { self.err("unexpected token") }
+    }
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        if let Some(b'[') | Some(b'{') = self.peek() {
+            if self.depth == MAX_DEPTH { return self.err("nesting too deep") }
+            self.depth += 1;
+            let val = self.nested();
+            self.depth -= 1;
+            return val;
+        }
+        match self.peek() {
+            Some(b'n') => self.keyword("null", Json::Null),
+            Some(b't') => self.keyword("true", Json::Bool(true)),
+            Some(b'f') => self.keyword("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => self.err("unexpected character"),
+            None => self.err("unexpected end of input"),
+        }
+    }
+    // an array or an object, one level deeper
+    fn nested(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_ws();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => { self.pos += 1; return Ok(Json::Array(items)) }
+                        _ => return self.err("expected `,` or `]`"),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_ws();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_ws();
+                    let key = self.string()?;
+                    self.skip_ws();
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_ws();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => { self.pos += 1; return Ok(Json::Object(fields)) }
+                        _ => return self.err("expected `,` or `}`"),
+                    }
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        if self.peek() == Some(b'-') { self.pos += 1; }
+        if self.digits() == 0 { return self.err("expected digits") }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if self.digits() == 0 { return self.err("expected digits") }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() { self.pos += 1; }
+            if self.digits() == 0 { return self.err("expected digits") }
+        }
+        Ok(Json::Number(String::from_utf8(self.s[start..self.pos].to_vec()).unwrap()))
+    }
+    fn hex4(&mut self) -> Result<u32, String> {
+        let hex = self.s.get(self.pos..self.pos + 4).and_then(|h| std::str::from_utf8(h).ok());
+        match hex.and_then(|h| u32::from_str_radix(h, 16).ok()) {
+            Some(c) => { self.pos += 4; Ok(c) }
+            None => self.err("bad \\u escape"),
+        }
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut buf = Vec::new();
+        loop {
+            match self.peek() {
+                None => return self.err("unterminated string"),
+                Some(b'"') => { self.pos += 1; break }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let mut c = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&c) && self.s[self.pos..].starts_with(b"\\u") {
+                                // only a low surrogate completes the pair; anything else is
+                                // left to be read as an escape of its own
+                                let start = self.pos;
+                                self.pos += 2;
+                                match self.hex4() {
+                                    Ok(low) if (0xdc00..0xe000).contains(&low) => c = 0x10000 + ((c - 0xd800) << 10) + (low - 0xdc00),
+                                    _ => self.pos = start,
+                                }
+                            }
+                            self.pos -= 1;
+                            std::char::from_u32(c).unwrap_or('\u{fffd}')
+                        }
+                        _ => return self.err("bad escape"),
+                    };
+                    self.pos += 1;
+                    let mut tmp = [0; 4];
+                    buf.extend_from_slice(c.encode_utf8(&mut tmp).as_bytes());
+                }
+                Some(c) => { self.pos += 1; buf.push(c) }
+            }
+        }
+        String::from_utf8(buf).or_else(|_| self.err("invalid utf-8"))
+    }
+}
+
+pub fn parse(s: &str) -> Result<Json, String> {
+    let mut p = Parser { s: s.as_bytes(), pos: 0, depth: 0 };
+    let val = p.value()?;
+    p.skip_ws();
+    if p.pos != p.s.len() { return p.err("trailing characters") }
+    Ok(val)
+}
+
+struct Error {
+    kind: &'static str,
+    message: String,
+}
+
+fn bad_request<T>(message: String) -> Result<T, Error> {
+    Err(Error { kind: "bad_request", message })
+}
+
+fn unsupported<T>(op: &str) -> Result<T, Error> {
+    Err(Error { kind: "unsupported", message: format!("`{}` is not supported by this cluster", op) })
+}
+
+impl From<ForestError> for Error {
+    fn from(e: ForestError) -> Error {
+        let kind = match e {
+            ForestError::UnknownVertex(_) => "unknown_vertex",
+            ForestError::SameVertex(_) => "same_vertex",
+            ForestError::AlreadyConnected(..) => "already_connected",
+            ForestError::NotConnected(..) => "not_connected",
+            ForestError::NoSuchEdge(..) => "no_such_edge",
+            ForestError::NotIsolated(_) => "not_isolated",
+            ForestError::Empty => "empty",
+        };
+        Error { kind, message: e.to_string() }
+    }
+}
+
+// numbers, strings and booleans are all passed to the cluster's text parser
+fn text<'a>(req: &'a Json, key: &str) -> Result<&'a str, Error> {
+    match req.get(key) {
+        Some(Json::Number(n)) => Ok(n),
+        Some(Json::String(s)) => Ok(s),
+        Some(Json::Bool(b)) => Ok(if *b { "true" } else { "false" }),
+        Some(_) => bad_request(format!("field `{}` must be a number, a string or a boolean", key)),
+        None => bad_request(format!("missing field `{}`", key)),
+    }
+}
+
+fn id(req: &Json, key: &str) -> Result<usize, Error> {
+    match req.get(key) {
+        Some(Json::Number(n)) => n.parse().or_else(|_| bad_request(format!("field `{}` must be a vertex id", key))),
+        Some(_) => bad_request(format!("field `{}` must be a vertex id", key)),
+        None => bad_request(format!("missing field `{}`", key)),
+    }
+}
+
+fn handle<T: ClusterCommands>(forest: &mut Forest<T>, req: &Json) -> Result<Json, Error> {
+    let op = match req.get("op") {
+        Some(Json::String(op)) => op.as_str(),
+        _ => return bad_request("missing string field `op`".to_string()),
+    };
+    let parse_err = |message: String| Error { kind: "bad_request", message };
+    match op {
+        "vertex" => {
+            let val = match req.get("value") {
+                None | Some(Json::Null) => T::V::default(),
+                Some(_) => T::value(text(req, "value")?).map_err(parse_err)?,
+            };
+            Ok(forest.add_vertex(val).into())
+        }
+        "pop_vertex" => Ok(forest.pop_vertex()?.into()),
+        "link" => {
+            let w = T::weight(text(req, "w")?).map_err(parse_err)?;
+            forest.link(id(req, "u")?, id(req, "v")?, w)?;
+            Ok(Json::Null)
+        }
+        "cut" => {
+            let (w, u_tree, v_tree) = forest.cut(id(req, "u")?, id(req, "v")?)?;
+            Ok(object(vec![("w", T::path_json(&w)), ("u", T::path_json(&u_tree)), ("v", T::path_json(&v_tree))]))
+        }
+        "set_edge" => {
+            let w = T::weight(text(req, "w")?).map_err(parse_err)?;
+            forest.set_edge(id(req, "u")?, id(req, "v")?, w)?;
+            Ok(Json::Null)
+        }
+        "set_vertex" => {
+            let val = T::value(text(req, "value")?).map_err(parse_err)?;
+            forest.set_vertex(id(req, "v")?, val)?;
+            Ok(Json::Null)
+        }
+        "mark" | "unmark" => match T::marked(op == "mark") {
+            Some(val) => {
+                forest.set_vertex(id(req, "v")?, val)?;
+                Ok(Json::Null)
+            }
+            None => unsupported(op),
+        },
+        "value" => Ok(T::value_json(&forest.value(id(req, "v")?)?)),
+        "connected" => Ok(forest.connected(id(req, "u")?, id(req, "v")?)?.into()),
+        "has_edge" | "edge" => {
+            let (u, v) = (id(req, "u")?, id(req, "v")?);
+            lookup(forest, u)?;
+            lookup(forest, v)?;
+            if op == "has_edge" {
+                Ok(forest.has_edge(u, v).into())
+            }
+            else {
+                Ok(forest.edge_value(u, v).map_or(Json::Null, |w| T::path_json(&w)))
+            }
+        }
+        "edges" => Ok(Json::Array(forest.edges_of(id(req, "v")?)?.into_iter().map(|(u, w)| object(vec![("v", u.into()), ("w", T::path_json(&w))])).collect())),
+        "path" => Ok(T::path_json(&forest.path(id(req, "u")?, id(req, "v")?)?)),
+        "path_inclusive" => Ok(T::path_json(&forest.path_inclusive(id(req, "u")?, id(req, "v")?)?)),
+        "component" => Ok(T::path_json(&forest.component(id(req, "v")?)?)),
+        "subtree" => Ok(T::path_json(&forest.subtree(id(req, "v")?, id(req, "p")?)?)),
+        _ => {
+            let args = match query_args(op) {
+                Some(args) => args,
+                None => return bad_request(format!("unknown op `{}`", op)),
+            };
+            let ids = args.iter().map(|key| id(req, key)).collect::<Result<Vec<_>, _>>()?;
+            match query(forest, op, &ids)? {
+                Some(answer) => Ok(Json::Object(answer.into_iter().map(|(k, a)| (k.to_string(), match a {
+                    Answer::Vertex(id) => id.into(),
+                    Answer::Number(n) => Json::Number(n),
+                    Answer::Nothing => Json::Null,
+                })).collect())),
+                None => unsupported(op),
+            }
+        }
+    }
+}
+
+/// Answers one JSON request per line with one JSON response per line:
+/// `{"ok":true,"result":...}` or `{"ok":false,"error":{"kind":...,"message":...}}`.
+/// An `id` field of the request is copied to the response. A line that is not
+/// UTF-8 gets a `parse` error. A failure to read the input gets an `io` error and
+/// ends the session; once `out` is closed the session ends quietly.
+pub fn run<T: ClusterCommands, R: BufRead, W: Write>(mut input: R, out: &mut W) {
+    let mut forest = Forest::<T>::new();
+    loop {
+        let mut stop = false;
+        let (req_id, res) = match read_line(&mut input) {
+            Ok(None) => break,
+            Ok(Some(Ok(line))) => {
+                if line.trim().is_empty() { continue }
+                match parse(&line) {
+                    Ok(req @ Json::Object(_)) => (req.get("id").cloned(), handle(&mut forest, &req)),
+                    Ok(_) => (None, bad_request("request must be an object".to_string())),
+                    Err(message) => (None, Err(Error { kind: "parse", message })),
+                }
+            }
+            Ok(Some(Err(message))) => (None, Err(Error { kind: "parse", message })),
+            Err(e) => {
+                stop = true;
+                (None, Err(Error { kind: "io", message: format!("failed to read input: {}", e) }))
+            }
+        };
+        let mut fields = Vec::new();
+        if let Some(req_id) = req_id {
+            fields.push(("id", req_id));
+        }
+        match res {
+            Ok(result) => {
+                fields.push(("ok", true.into()));
+                fields.push(("result", result));
+            }
+            Err(e) => {
+                fields.push(("ok", false.into()));
+                fields.push(("error", object(vec![("kind", e.kind.into()), ("message", e.message.as_str().into())])));
+            }
+        }
+        if writeln!(out, "{}", object(fields)).and_then(|_| out.flush()).is_err() || stop { return }
+    }
+}
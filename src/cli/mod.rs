@@ -0,0 +1,308 @@
+use std::fmt::Debug;
+use std::io::{self, BufRead};
+use toptree::node::*;
+use toptree::forest::*;
+use toptree::query::diameter::*;
+use toptree::query::center::*;
+use toptree::query::median::*;
+use toptree::query::nearest_marked::*;
+use toptree::select::*;
+
+pub mod script;
+pub mod repl;
+pub mod json;
+
+/// How vertices are written in commands and answers.
+pub trait Naming {
+    fn resolve(&self, s: &str) -> Result<usize, String>;
+    fn name(&self, id: usize) -> String;
+}
+
+/// Vertices are written as their ids.
+pub struct Ids;
+
+impl Naming for Ids {
+    fn resolve(&self, s: &str) -> Result<usize, String> { parse_usize(s) }
+    fn name(&self, id: usize) -> String { id.to_string() }
+}
+
+/// One field of a query answer.
+pub enum Answer {
+    Vertex(usize),
+    Number(String),
+    /// no vertex or no distance, written `-` in scripts and `null` in JSON
+    Nothing,
+}
+
+/// The cluster specific commands and the names of the vertices they take,
+/// which are also the fields of their JSON requests.
+pub const QUERIES: [(&str, &[&str]); 7] = [
+    ("diameter", &["v"]),
+    ("farthest", &["v"]),
+    ("center", &["v"]),
+    ("bicenter", &["v"]),
+    ("median", &["v"]),
+    ("nearest_marked", &["v"]),
+    ("select_in_subtree", &["v", "p"]),
+];
+
+fn query_args(op: &str) -> Option<&'static [&'static str]> {
+    QUERIES.iter().find(|(name, _)| *name == op).map(|(_, args)| *args)
+}
+
+/// Glue between a built-in cluster and the text commands.
+/// `query` answers the cluster specific commands of `QUERIES` as named fields
+/// and returns `None` for the ones this cluster cannot answer. `vs` holds one
+/// vertex per argument; for `select_in_subtree` they are adjacent. `marked`
+/// is the vertex value of `mark` and `unmark`, `None` if the cluster has none.
+pub trait ClusterCommands: Cluster + Debug {
+    fn weight(s: &str) -> Result<Self, String>;
+    fn value(s: &str) -> Result<Self::V, String>;
+    fn fmt_path(path: &Self) -> String;
+    fn path_json(path: &Self) -> json::Json;
+    fn value_json(val: &Self::V) -> json::Json;
+    fn query(_forest: &Forest<Self>, _op: &str, _vs: &[Vertex<Self>]) -> Option<Vec<(&'static str, Answer)>> { None }
+    fn marked(_mark: bool) -> Option<Self::V> { None }
+}
+
+fn parse_usize(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("expected a non-negative integer, found `{}`", s))
+}
+
+// the next line of `input` without its line break, `None` at the end of input.
+// A line that is not UTF-8 is an error for that line only and reading can go on.
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<Option<Result<String, String>>> {
+    let mut buf = Vec::new();
+    if input.read_until(b'\n', &mut buf)? == 0 { return Ok(None) }
+    if buf.last() == Some(&b'\n') { buf.pop(); }
+    if buf.last() == Some(&b'\r') { buf.pop(); }
+    Ok(Some(String::from_utf8(buf).map_err(|e| format!("invalid utf-8 at byte {}", e.utf8_error().valid_up_to()))))
+}
+
+// the handles are only used for one command while the forest is borrowed,
+// and never linked or cut behind its back
+fn lookup<T: Cluster>(forest: &Forest<T>, id: usize) -> Result<Vertex<T>, ForestError> {
+    unsafe { forest.vertex(id) }
+}
+
+fn vertex<T: Cluster>(forest: &Forest<T>, v: Vertex<T>) -> Answer {
+    Answer::Vertex(forest.id(v).unwrap())
+}
+
+// `Nothing` for a dummy vertex
+fn end<T: Cluster>(forest: &Forest<T>, v: Vertex<T>) -> Answer {
+    if v.is_dummy() { Answer::Nothing } else { vertex(forest, v) }
+}
+
+fn number<N: ToString>(n: N) -> Answer {
+    Answer::Number(n.to_string())
+}
+
+impl ClusterCommands for usize {
+    fn weight(s: &str) -> Result<Self, String> { parse_usize(s) }
+    fn value(s: &str) -> Result<usize, String> { parse_usize(s) }
+    fn fmt_path(path: &Self) -> String { path.to_string() }
+    fn path_json(path: &Self) -> json::Json { json::Json::from(*path) }
+    fn value_json(val: &usize) -> json::Json { json::Json::from(*val) }
+}
+
+impl ClusterCommands for Diameter {
+    fn weight(s: &str) -> Result<Self, String> { parse_usize(s).map(Diameter::new) }
+    fn value(s: &str) -> Result<usize, String> { parse_usize(s) }
+    fn fmt_path(path: &Self) -> String { path.length.to_string() }
+    fn path_json(path: &Self) -> json::Json {
+        json::object(vec![
+            ("diam", path.diam.into()),
+            ("max_dist_left", path.max_dist_left.into()),
+            ("max_dist_right", path.max_dist_right.into()),
+            ("length", path.length.into()),
+        ])
+    }
+    fn value_json(val: &usize) -> json::Json { json::Json::from(*val) }
+    fn query(forest: &Forest<Self>, op: &str, vs: &[Vertex<Self>]) -> Option<Vec<(&'static str, Answer)>> {
+        match op {
+            "diameter" => {
+                let (x, y, d) = diameter(vs[0]);
+                Some(vec![("u", vertex(forest, x)), ("v", vertex(forest, y)), ("length", number(d))])
+            }
+            "farthest" => {
+                let (x, d) = farthest(vs[0]);
+                Some(vec![("vertex", vertex(forest, x)), ("distance", number(d))])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ClusterCommands for Center {
+    fn weight(s: &str) -> Result<Self, String> { parse_usize(s).map(Center::new) }
+    fn value(s: &str) -> Result<usize, String> { parse_usize(s) }
+    fn fmt_path(path: &Self) -> String { path.length.to_string() }
+    fn path_json(path: &Self) -> json::Json {
+        json::object(vec![
+            ("max_dist_left", path.max_dist_left.into()),
+            ("max_dist_right", path.max_dist_right.into()),
+            ("length", path.length.into()),
+        ])
+    }
+    fn value_json(val: &usize) -> json::Json { json::Json::from(*val) }
+    fn query(forest: &Forest<Self>, op: &str, vs: &[Vertex<Self>]) -> Option<Vec<(&'static str, Answer)>> {
+        match op {
+            "center" => {
+                let (c, r) = center(vs[0]);
+                Some(vec![("vertex", vertex(forest, c)), ("radius", number(r))])
+            }
+            "bicenter" => {
+                let (c, other, r) = bicenter(vs[0]);
+                Some(vec![("vertex", vertex(forest, c)), ("other", other.map_or(Answer::Nothing, |o| vertex(forest, o))), ("radius", number(r))])
+            }
+            "select_in_subtree" => {
+                let (x, y) = select_in_subtree(vs[0], vs[1], toward_center);
+                Some(vec![("u", end(forest, x)), ("v", end(forest, y))])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ClusterCommands for Median {
+    fn weight(s: &str) -> Result<Self, String> { parse_usize(s).map(Median::new) }
+    fn value(s: &str) -> Result<usize, String> { parse_usize(s) }
+    fn fmt_path(path: &Self) -> String { path.length.to_string() }
+    fn path_json(path: &Self) -> json::Json {
+        json::object(vec![
+            ("inter_weight", path.inter_weight.into()),
+            ("left_sum", path.left_sum.into()),
+            ("right_sum", path.right_sum.into()),
+            ("length", path.length.into()),
+        ])
+    }
+    fn value_json(val: &usize) -> json::Json { json::Json::from(*val) }
+    fn query(forest: &Forest<Self>, op: &str, vs: &[Vertex<Self>]) -> Option<Vec<(&'static str, Answer)>> {
+        match op {
+            "median" => {
+                let (m, cost) = weighted_median(vs[0]);
+                Some(vec![("vertex", vertex(forest, m)), ("cost", number(cost))])
+            }
+            "select_in_subtree" => {
+                let (x, y) = select_in_subtree(vs[0], vs[1], toward_median);
+                Some(vec![("u", end(forest, x)), ("v", end(forest, y))])
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ClusterCommands for NearestMarked {
+    fn weight(s: &str) -> Result<Self, String> { parse_usize(s).map(NearestMarked::new) }
+    fn value(s: &str) -> Result<bool, String> {
+        match s {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(format!("expected `true` or `false`, found `{}`", s)),
+        }
+    }
+    fn fmt_path(path: &Self) -> String { path.length.to_string() }
+    fn path_json(path: &Self) -> json::Json {
+        let near = |d: Option<usize>| d.map_or(json::Json::Null, json::Json::from);
+        json::object(vec![
+            ("near_left", near(path.near_left)),
+            ("near_right", near(path.near_right)),
+            ("length", path.length.into()),
+        ])
+    }
+    fn value_json(val: &bool) -> json::Json { json::Json::from(*val) }
+    fn query(forest: &Forest<Self>, op: &str, vs: &[Vertex<Self>]) -> Option<Vec<(&'static str, Answer)>> {
+        match op {
+            "nearest_marked" => match nearest_marked(vs[0]) {
+                Some((x, d)) => Some(vec![("vertex", vertex(forest, x)), ("distance", number(d))]),
+                None => Some(vec![("vertex", Answer::Nothing), ("distance", Answer::Nothing)]),
+            },
+            _ => None,
+        }
+    }
+    fn marked(mark: bool) -> Option<bool> { Some(mark) }
+}
+
+pub const CLUSTERS: [&str; 5] = ["length", "diameter", "center", "median", "nearest"];
+
+/// Runs `f` with the cluster type named by `--cluster`.
+macro_rules! with_cluster {
+    ($name:expr, $f:ident ( $($arg:expr),* )) => {
+        match $name {
+            "length" => Some($f::<usize>($($arg),*)),
+            "diameter" => Some($f::<toptree::query::diameter::Diameter>($($arg),*)),
+            "center" => Some($f::<toptree::query::center::Center>($($arg),*)),
+            "median" => Some($f::<toptree::query::median::Median>($($arg),*)),
+            "nearest" => Some($f::<toptree::query::nearest_marked::NearestMarked>($($arg),*)),
+            _ => None,
+        }
+    };
+}
+
+// runs the `QUERIES` command `op` on the vertices `ids`, checking that they
+// exist and, for `select_in_subtree`, that they are adjacent
+fn query<T: ClusterCommands>(forest: &Forest<T>, op: &str, ids: &[usize]) -> Result<Option<Vec<(&'static str, Answer)>>, ForestError> {
+    let vs = ids.iter().map(|&id| lookup(forest, id)).collect::<Result<Vec<_>, _>>()?;
+    if op == "select_in_subtree" && !forest.has_edge(ids[0], ids[1]) {
+        return Err(ForestError::NoSuchEdge(ids[0], ids[1]));
+    }
+    Ok(T::query(forest, op, &vs))
+}
+
+/// Executes one command. Returns the answer for queries and `None` for updates.
+pub fn exec<T: ClusterCommands>(forest: &mut Forest<T>, names: &dyn Naming, words: &[&str]) -> Result<Option<String>, String> {
+    let arity = |n: usize| {
+        if words.len() == n + 1 { Ok(()) }
+        else { Err(format!("`{}` takes {} argument(s)", words[0], n)) }
+    };
+    let vertex = |i: usize| names.resolve(words[i]);
+    match words[0] {
+        "vertex" => {
+            let val = match words.len() {
+                1 => T::V::default(),
+                2 => T::value(words[1])?,
+                _ => return Err("`vertex` takes at most 1 argument".to_string()),
+            };
+            forest.add_vertex(val);
+            Ok(None)
+        }
+        "link" => {
+            arity(3)?;
+            forest.link(vertex(1)?, vertex(2)?, T::weight(words[3])?).map_err(|e| e.to_string())?;
+            Ok(None)
+        }
+        "cut" => {
+            arity(2)?;
+            forest.cut(vertex(1)?, vertex(2)?).map_err(|e| e.to_string())?;
+            Ok(None)
+        }
+        "path" => {
+            arity(2)?;
+            let path = forest.path(vertex(1)?, vertex(2)?).map_err(|e| e.to_string())?;
+            Ok(Some(T::fmt_path(&path)))
+        }
+        "set-edge" => {
+            arity(3)?;
+            forest.set_edge(vertex(1)?, vertex(2)?, T::weight(words[3])?).map_err(|e| e.to_string())?;
+            Ok(None)
+        }
+        "set-vertex" => {
+            arity(2)?;
+            forest.set_vertex(vertex(1)?, T::value(words[2])?).map_err(|e| e.to_string())?;
+            Ok(None)
+        }
+        op => {
+            let args = query_args(op).ok_or_else(|| format!("unknown command `{}`", op))?;
+            arity(args.len())?;
+            let ids = (1..=args.len()).map(vertex).collect::<Result<Vec<_>, _>>()?;
+            let answer = query(forest, op, &ids).map_err(|e| e.to_string())?.ok_or_else(|| format!("`{}` is not supported by this cluster", op))?;
+            let words: Vec<String> = answer.into_iter().map(|(_, a)| match a {
+                Answer::Vertex(id) => names.name(id),
+                Answer::Number(n) => n,
+                Answer::Nothing => "-".to_string(),
+            }).collect();
+            Ok(Some(words.join(" ")))
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use toptree::forest::*;
+use toptree::debug::*;
+use super::*;
+
+const HELP: &str = "commands:
+  vertex [NAME] [VALUE]   add a vertex, optionally named
+  link U V W | cut U V | set-edge U V W | set-vertex V VALUE
+  path U V | diameter V | farthest V | center V | bicenter V | median V
+  select_in_subtree V P | nearest_marked V
+  show V                  print the cluster tree containing V
+  validate [V]            check the cluster trees (of V, or of every vertex)
+  undo                    revert the last update
+  history                 list the commands entered so far
+  help | quit
+vertices are written as their name or their id";
+
+struct Names {
+    ids: HashMap<String, usize>,
+    names: Vec<Option<String>>,
+}
+
+impl Naming for Names {
+    fn resolve(&self, s: &str) -> Result<usize, String> {
+        match self.ids.get(s) {
+            Some(&id) => Ok(id),
+            None => s.parse().map_err(|_| format!("unknown vertex `{}`", s)),
+        }
+    }
+    fn name(&self, id: usize) -> String {
+        match self.names.get(id) {
+            Some(Some(name)) => name.clone(),
+            _ => id.to_string(),
+        }
+    }
+}
+
+enum Undo<T: Cluster> {
+    Vertex,
+    Link(usize, usize),
+    Cut(usize, usize, T),
+    SetEdge(usize, usize, T),
+    SetVertex(usize, T::V),
+}
+
+struct Repl<T: ClusterCommands> {
+    forest: Forest<T>,
+    names: Names,
+    undo: Vec<(String, Undo<T>)>,
+    history: Vec<String>,
+}
+
+impl<T: ClusterCommands> Repl<T> {
+    fn new() -> Self {
+        Repl {
+            forest: Forest::new(),
+            names: Names { ids: HashMap::new(), names: Vec::new() },
+            undo: Vec::new(),
+            history: Vec::new(),
+        }
+    }
+
+    fn add_vertex(&mut self, words: &[&str]) -> Result<Undo<T>, String> {
+        let (name, val) = match words.len() {
+            1 => (None, T::V::default()),
+            2 => match T::value(words[1]) {
+                Ok(val) => (None, val),
+                Err(_) => (Some(words[1]), T::V::default()),
+            },
+            3 => (Some(words[1]), T::value(words[2])?),
+            _ => return Err("`vertex` takes at most 2 arguments".to_string()),
+        };
+        if let Some(name) = name {
+            if name.parse::<usize>().is_ok() || self.names.ids.contains_key(name) {
+                return Err(format!("`{}` cannot be used as a new name", name));
+            }
+        }
+        let id = self.forest.add_vertex(val);
+        if let Some(name) = name {
+            self.names.ids.insert(name.to_string(), id);
+        }
+        self.names.names.push(name.map(|s| s.to_string()));
+        Ok(Undo::Vertex)
+    }
+
+    // runs an update through `exec` and returns how to revert it
+    fn update(&mut self, words: &[&str]) -> Result<Undo<T>, String> {
+        let names = &self.names;
+        let vertex = |i: usize| words.get(i).ok_or_else(|| format!("`{}` needs more arguments", words[0])).and_then(|w| names.resolve(w));
+        let undo = match words[0] {
+            "link" => Undo::Link(vertex(1)?, vertex(2)?),
+            "cut" | "set-edge" => {
+                let (u, v) = (vertex(1)?, vertex(2)?);
+                let w = self.forest.edge_value(u, v).ok_or(ForestError::NoSuchEdge(u, v).to_string())?;
+                if words[0] == "cut" { Undo::Cut(u, v, w) } else { Undo::SetEdge(u, v, w) }
+            }
+            "set-vertex" => {
+                let v = vertex(1)?;
+                Undo::SetVertex(v, self.forest.value(v).map_err(|e| e.to_string())?)
+            }
+            _ => unreachable!(),
+        };
+        exec(&mut self.forest, names, words)?;
+        Ok(undo)
+    }
+
+    // the entry stays on the undo stack if reverting it fails
+    fn revert(&mut self) -> Result<String, String> {
+        let (_, undo) = self.undo.last().ok_or("nothing to undo")?;
+        let res = match undo {
+            Undo::Vertex => self.forest.pop_vertex().map(|id| {
+                if let Some(Some(name)) = self.names.names.pop() {
+                    self.names.ids.remove(&name);
+                }
+                debug_assert!(id == self.names.names.len());
+            }),
+            Undo::Link(u, v) => self.forest.cut(*u, *v).map(|_| ()),
+            Undo::Cut(u, v, w) => self.forest.link(*u, *v, w.clone()),
+            Undo::SetEdge(u, v, w) => self.forest.set_edge(*u, *v, w.clone()),
+            Undo::SetVertex(v, val) => self.forest.set_vertex(*v, val.clone()),
+        };
+        res.map_err(|e| e.to_string())?;
+        let (line, _) = self.undo.pop().unwrap();
+        Ok(format!("undid `{}`", line))
+    }
+
+    fn validate(&self, words: &[&str]) -> Result<String, String> {
+        let ids: Vec<usize> = match words.len() {
+            1 => (0..self.forest.len()).collect(),
+            2 => vec![self.names.resolve(words[1])?],
+            _ => return Err("`validate` takes at most 1 argument".to_string()),
+        };
+        for id in ids {
+            let v = lookup(&self.forest, id).map_err(|e| e.to_string())?;
+            validate_cluster_tree(v).map_err(|e| format!("{}: {}", self.names.name(id), e))?;
+        }
+        Ok("ok".to_string())
+    }
+
+    fn step(&mut self, line: &str) -> Result<Option<String>, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[0] {
+            "help" => Ok(Some(HELP.to_string())),
+            "history" => Ok(Some(self.history.iter().enumerate().map(|(i, l)| format!("{:4}  {}", i + 1, l)).collect::<Vec<_>>().join("\n"))),
+            "undo" => self.revert().map(Some),
+            "validate" => self.validate(&words).map(Some),
+            "show" => {
+                if words.len() != 2 { return Err("`show` takes 1 argument".to_string()) }
+                let v = lookup(&self.forest, self.names.resolve(words[1])?).map_err(|e| e.to_string())?;
+                let name = |v| self.forest.id(v).map_or_else(|| "?".to_string(), |id| self.names.name(id));
+                Ok(Some(fmt_cluster_tree_with(v, name).trim_end().to_string()))
+            }
+            "vertex" => {
+                let undo = self.add_vertex(&words)?;
+                self.undo.push((line.to_string(), undo));
+                Ok(None)
+            }
+            "link" | "cut" | "set-edge" | "set-vertex" => {
+                let undo = self.update(&words)?;
+                self.undo.push((line.to_string(), undo));
+                Ok(None)
+            }
+            _ => exec(&mut self.forest, &self.names, &words),
+        }
+    }
+}
+
+/// Reads commands interactively until `quit`, end of input or a failure to
+/// read it. A line that is not UTF-8 is an error. Stops quietly once `out` is closed.
+pub fn run<T: ClusterCommands, R: BufRead, W: Write>(mut input: R, out: &mut W) {
+    let mut repl = Repl::<T>::new();
+    loop {
+        if write!(out, "> ").and_then(|_| out.flush()).is_err() { return }
+        let res = match read_line(&mut input) {
+            Ok(None) => break,
+            Ok(Some(Ok(line))) => {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') { continue }
+                if line == "quit" || line == "exit" { break }
+                repl.history.push(line.to_string());
+                repl.step(line)
+            }
+            Ok(Some(Err(msg))) => Err(msg),
+            Err(e) => {
+                let _ = writeln!(out, "\nerror: failed to read input: {}", e);
+                return;
+            }
+        };
+        let written = match res {
+            Ok(Some(ans)) => writeln!(out, "{}", ans),
+            Ok(None) => Ok(()),
+            Err(msg) => writeln!(out, "error: {}", msg),
+        };
+        if written.is_err() { return }
+    }
+    let _ = writeln!(out);
+}
@@ -0,0 +1,39 @@
+use std::io::{BufRead, Write};
+use toptree::forest::*;
+use super::*;
+
+/// Runs a script, one command per line. Blank lines and lines starting with `#`
+/// are skipped. Answers go to `out`, errors go to `err` with their line number
+/// and do not stop the script, except for a failure to read the input. A line
+/// that is not UTF-8 is an error. Stops quietly once `out` or `err` is closed.
+/// Returns the number of errors.
+pub fn run<T: ClusterCommands, R: BufRead, W: Write, E: Write>(mut input: R, out: &mut W, err: &mut E) -> usize {
+    let mut forest = Forest::<T>::new();
+    let mut errors = 0;
+    for i in 1.. {
+        let res = match read_line(&mut input) {
+            Ok(None) => break,
+            Ok(Some(Ok(line))) => {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                if words.is_empty() || words[0].starts_with('#') { continue }
+                exec(&mut forest, &Ids, &words)
+            }
+            Ok(Some(Err(msg))) => Err(msg),
+            Err(e) => {
+                errors += 1;
+                let _ = writeln!(err, "line {}: failed to read input: {}", i, e);
+                break;
+            }
+        };
+        let written = match res {
+            Ok(Some(ans)) => writeln!(out, "{}", ans),
+            Ok(None) => Ok(()),
+            Err(msg) => {
+                errors += 1;
+                writeln!(err, "line {}: {}", i, msg)
+            }
+        };
+        if written.is_err() { break }
+    }
+    errors
+}
@@ -2,15 +2,20 @@ use std::ptr::NonNull;
 use crate::node::*;
 use crate::expose::*;
 use crate::splay::*;
+use crate::path_query::find_edge;
+use crate::fold::root_fold;
 
-fn bring<T: Cluster>(mut root: NonNull<Compress<T>>) {
+// drops the right child of `root` and returns the new root of its tree
+fn bring<T: Cluster>(mut root: NonNull<Compress<T>>) -> CompNode<T> {
     unsafe {
         match root.as_ref().rake() {
             None => {
                 let mut left = root.as_ref().child(0);
                 let _ = Box::from_raw(root.as_ptr());
+                stat!(frees);
                 *left.parent_mut() = None;
                 left.fix();
+                left
             }
             Some(RakeNode::Leaf(mut new_right)) => {
                 new_right.reverse();
@@ -23,6 +28,7 @@ fn bring<T: Cluster>(mut root: NonNull<Compress<T>>) {
 
                 new_right.fix();
                 root.as_mut().fix();
+                CompNode::Node(root)
             }
             Some(RakeNode::Node(mut rake)) => {
                 rake.as_mut().push();
@@ -40,6 +46,7 @@ fn bring<T: Cluster>(mut root: NonNull<Compress<T>>) {
                 else { unreachable!() };
 
                 let _ = Box::from_raw(rake.as_ptr());
+                stat!(frees);
                 
                 new_right.reverse();
                 new_right.push();
@@ -53,12 +60,26 @@ fn bring<T: Cluster>(mut root: NonNull<Compress<T>>) {
                 new_rake.fix();
                 new_right.fix();
                 root.as_mut().fix();
+                CompNode::Node(root)
             }
         }
     }
 }
 
-pub fn cut<T: Cluster>(v: Vertex<T>, u: Vertex<T>) {
+/// Removes the edge `v`-`u` and returns its weight oriented from `v` to `u`,
+/// followed by the folds of the two resulting trees with `v` and `u` at
+/// `endpoints(0)`, as `component_fold` would give them.
+///
+/// Panics if there is no such edge.
+pub fn cut<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> (T, T, T) {
+    stat_op!(Cut);
+    assert!(find_edge(v, u).is_some(), "cut: the vertices are not adjacent");
+    cut_raw(v, u)
+}
+
+// `v` and `u` must be adjacent
+pub(crate) fn cut_raw<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> (T, T, T) {
+    stat_op!(Cut);
     unsafe {
         soft_expose(v, u);
         let mut root = v.handle().unwrap();
@@ -72,9 +93,13 @@ pub fn cut<T: Cluster>(v: Vertex<T>, u: Vertex<T>) {
             right.push();
 
             if let CompNode::Node(right) = right {
-                if let CompNode::Leaf(_) = right.as_ref().child(1) {
-                    bring(right);
-                    bring(root);
+                if let CompNode::Leaf(e) = right.as_ref().child(1) {
+                    let weight = e.as_ref().weight_from(v);
+                    let u_root = bring(right);
+                    let v_root = bring(root);
+                    let _ = Box::from_raw(e.as_ptr());
+                    stat!(frees);
+                    (weight, root_fold(v_root, v), root_fold(u_root, u))
                 }
                 else { unreachable!() }
             }
@@ -1,16 +1,16 @@
 use crate::node::*;
 
 
-pub fn test_comp_endpoints<T: Cluster + std::fmt::Debug>(node: CompNode<T>) where T::V: Copy + std::fmt::Debug {
+pub(crate) fn test_comp_set<T: Cluster + std::fmt::Debug>(mut node: CompNode<T>) {
     unsafe {
-        //node.push();
+        node.push();
         match node {
             CompNode::Node(n) => {
                 println!("NODE {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
                 println!("left");
-                test_comp_endpoints(n.as_ref().child(0));
+                test_comp_print(n.as_ref().child(0));
                 println!("right");
-                test_comp_endpoints(n.as_ref().child(1));
+                test_comp_print(n.as_ref().child(1));
             }
             CompNode::Leaf(_) => {
                 println!("LEAF {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
@@ -19,31 +19,159 @@ pub fn test_comp_endpoints<T: Cluster + std::fmt::Debug>(node: CompNode<T>) wher
     }
 }
 
-pub fn test_comp_set<T: Cluster + std::fmt::Debug>(mut node: CompNode<T>) where T::V: Copy + std::fmt::Debug {
+pub(crate) fn test_comp_print<T: Cluster + std::fmt::Debug>(node: CompNode<T>) {
+    match node {
+        CompNode::Node(_) => {
+            println!("NODE {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
+        }
+        CompNode::Leaf(_) => {
+            println!("LEAF {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
+        }
+    }
+}
+
+use std::fmt::Write;
+
+fn fmt_vertex<T: Cluster>(v: Vertex<T>) -> String {
+    fmt_vertex_with(v, &|v| format!("{:?}", v.value()))
+}
+
+fn fmt_vertex_with<T: Cluster>(v: Vertex<T>, name: &dyn Fn(Vertex<T>) -> String) -> String {
+    if v.is_dummy() { "dummy".to_string() } else { name(v) }
+}
+
+fn fmt_comp<T: Cluster + std::fmt::Debug>(out: &mut String, node: CompNode<T>, name: &dyn Fn(Vertex<T>) -> String, label: &str, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let (a, b) = (fmt_vertex_with(node.endpoints(0), name), fmt_vertex_with(node.endpoints(1), name));
     unsafe {
-        node.push();
         match node {
             CompNode::Node(n) => {
-                println!("NODE {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
-                println!("left");
-                test_comp_print(n.as_ref().child(0));
-                println!("right");
-                test_comp_print(n.as_ref().child(1));
+                let n = n.as_ref();
+                writeln!(out, "{}{}Compress [{} - {}] rev={} guard={} fold={:?}", indent, label, a, b, n.rev(), n.guard, n.fold).unwrap();
+                fmt_comp(out, n.child(0), name, "left: ", depth + 1);
+                fmt_comp(out, n.child(1), name, "right: ", depth + 1);
+                if let Some(r) = n.rake() {
+                    fmt_rake(out, r, name, "rake: ", depth + 1);
+                }
             }
             CompNode::Leaf(_) => {
-                println!("LEAF {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
+                writeln!(out, "{}{}Edge [{} - {}] fold={:?}", indent, label, a, b, node.fold()).unwrap();
             }
         }
     }
 }
 
-pub fn test_comp_print<T: Cluster + std::fmt::Debug>(node: CompNode<T>) where T::V: Copy + std::fmt::Debug {
+fn fmt_rake<T: Cluster + std::fmt::Debug>(out: &mut String, node: RakeNode<T>, name: &dyn Fn(Vertex<T>) -> String, label: &str, depth: usize) {
     match node {
-        CompNode::Node(_) => {
-            println!("NODE {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
+        RakeNode::Node(n) => {
+            let indent = "  ".repeat(depth);
+            let (a, b) = (fmt_vertex_with(node.endpoints(0), name), fmt_vertex_with(node.endpoints(1), name));
+            writeln!(out, "{}{}Rake [{} - {}] fold={:?}", indent, label, a, b, node.fold()).unwrap();
+            unsafe {
+                fmt_rake(out, n.as_ref().child(0), name, "left: ", depth + 1);
+                fmt_rake(out, n.as_ref().child(1), name, "right: ", depth + 1);
+            }
         }
-        CompNode::Leaf(_) => {
-            println!("LEAF {:?} = {:?}", [node.endpoints(0), node.endpoints(1)].iter().map(|v| v.value()).collect::<Vec<_>>(), node.fold());
+        RakeNode::Leaf(c) => fmt_comp(out, c, name, label, depth),
+    }
+}
+
+fn root_of<T: Cluster>(v: Vertex<T>) -> Option<CompNode<T>> {
+    let mut node = v.handle()?;
+    let mut par = node.parent();
+    while let Some(p) = par {
+        par = p.parent();
+        if let ParentNode::Compress(c) = p {
+            node = CompNode::Node(c);
         }
     }
+    Some(node)
+}
+
+/// Renders the whole cluster tree containing `v`, one node per line.
+/// The tree is not modified, so pending reversals are not pushed:
+/// children of a node with `rev=true` are shown in their stored orientation.
+pub fn fmt_cluster_tree<T: Cluster + std::fmt::Debug>(v: Vertex<T>) -> String {
+    fmt_cluster_tree_with(v, |v| format!("{:?}", v.value()))
+}
+
+/// Like `fmt_cluster_tree`, but writes each vertex other than the dummies as `name(v)`.
+pub fn fmt_cluster_tree_with<T: Cluster + std::fmt::Debug, F: Fn(Vertex<T>) -> String>(v: Vertex<T>, name: F) -> String {
+    let mut out = String::new();
+    if let Some(root) = root_of(v) {
+        fmt_comp(&mut out, root, &name, "", 0);
+    }
+    out
+}
+
+fn same_fold<T: Cluster + std::fmt::Debug>(stored: &T, expected: &T) -> Result<(), String> {
+    let (a, b) = (format!("{:?}", stored), format!("{:?}", expected));
+    if a == b { Ok(()) } else { Err(format!("stale fold {} (expected {})", a, b)) }
+}
+
+fn validate_comp<T: Cluster + std::fmt::Debug>(node: CompNode<T>, parent: Option<ParentNode<T>>) -> Result<usize, String> {
+    if node.parent() != parent {
+        return Err(format!("wrong parent pointer at [{} - {}]", fmt_vertex(node.endpoints(0)), fmt_vertex(node.endpoints(1))));
+    }
+    let n = match node {
+        CompNode::Node(n) => n,
+        CompNode::Leaf(_) => return Ok(1),
+    };
+    let comp = unsafe { n.as_ref() };
+    let (c0, c1) = (comp.child(0), comp.child(1));
+    let mut count = 1 + validate_comp(c0, Some(ParentNode::Compress(n)))? + validate_comp(c1, Some(ParentNode::Compress(n)))?;
+    let mid = c0.endpoints(1);
+    if c1.endpoints(0) != mid {
+        return Err(format!("children of a compress meet at {} and {}", fmt_vertex(mid), fmt_vertex(c1.endpoints(0))));
+    }
+    if mid.handle() != Some(node) {
+        return Err(format!("vertex {} is not handled by the compress it is the middle of", fmt_vertex(mid)));
+    }
+    let (a, b) = if comp.rev() { (c1.endpoints(1), c0.endpoints(0)) } else { (c0.endpoints(0), c1.endpoints(1)) };
+    if node.endpoints(0) != a || node.endpoints(1) != b {
+        return Err(format!("compress [{} - {}] does not match its children", fmt_vertex(node.endpoints(0)), fmt_vertex(node.endpoints(1))));
+    }
+    let left = match comp.rake() {
+        Some(r) => {
+            count += validate_rake(r, Some(ParentNode::Compress(n)), mid)?;
+            raked(c0.fold(), r.fold(), c0.endpoints(0).value_ref(), r.endpoints(0).value_ref(), mid.value_ref())
+        }
+        None => c0.fold().clone(),
+    };
+    let mut fold = compressed(&left, c1.fold(), c0.endpoints(0).value_ref(), c1.endpoints(1).value_ref(), mid.value_ref());
+    if comp.rev() { fold.reverse(); }
+    same_fold(&comp.fold, &fold)?;
+    Ok(count)
+}
+
+fn validate_rake<T: Cluster + std::fmt::Debug>(node: RakeNode<T>, parent: Option<ParentNode<T>>, attach: Vertex<T>) -> Result<usize, String> {
+    if node.endpoints(1) != attach {
+        return Err(format!("raked cluster hangs from {} instead of {}", fmt_vertex(node.endpoints(1)), fmt_vertex(attach)));
+    }
+    match node {
+        RakeNode::Node(r) => {
+            if unsafe { r.as_ref().parent() } != parent {
+                return Err(format!("wrong parent pointer at rake [{} - {}]", fmt_vertex(node.endpoints(0)), fmt_vertex(attach)));
+            }
+            let (r0, r1) = unsafe { (r.as_ref().child(0), r.as_ref().child(1)) };
+            let count = 1 + validate_rake(r0, Some(ParentNode::Rake(r)), attach)? + validate_rake(r1, Some(ParentNode::Rake(r)), attach)?;
+            if node.endpoints(0) != r0.endpoints(0) {
+                return Err(format!("rake [{} - {}] does not match its children", fmt_vertex(node.endpoints(0)), fmt_vertex(attach)));
+            }
+            let fold = raked(r0.fold(), r1.fold(), r0.endpoints(0).value_ref(), r1.endpoints(0).value_ref(), attach.value_ref());
+            same_fold(node.fold(), &fold)?;
+            Ok(count)
+        }
+        RakeNode::Leaf(c) => validate_comp(c, parent),
+    }
+}
+
+/// Checks the cluster tree containing `v` without modifying it: parent pointers,
+/// shared boundary vertices, handles of middle vertices and every stored fold.
+/// Folds are compared through their `Debug` output. Returns the number of nodes.
+pub fn validate_cluster_tree<T: Cluster + std::fmt::Debug>(v: Vertex<T>) -> Result<usize, String> {
+    match root_of(v) {
+        Some(root) => validate_comp(root, None),
+        None => Err(format!("vertex {} has no handle", fmt_vertex(v))),
+    }
 }
@@ -1,9 +1,12 @@
 use crate::node::*;
 use crate::parent_dir::*;
 use crate::splay::*;
+use crate::select::assert_not_selecting;
 
-pub fn expose_raw<T: Cluster>(mut node: CompNode<T>) -> CompNode<T> {
+pub(crate) fn expose_raw<T: Cluster>(mut node: CompNode<T>) -> CompNode<T> {
+    assert_not_selecting();
     loop {
+        stat!(expose_iters);
         if let CompNode::Node(comp) = node {
             splay_comp(comp);
         }
@@ -80,11 +83,20 @@ pub fn expose_raw<T: Cluster>(mut node: CompNode<T>) -> CompNode<T> {
     node
 }
 
-pub fn expose<T: Cluster>(ver: Vertex<T>) -> CompNode<T> {
+pub(crate) fn expose<T: Cluster>(ver: Vertex<T>) -> CompNode<T> {
     expose_raw(ver.handle().unwrap())
 }
 
-pub fn soft_expose<T: Cluster>(v: Vertex<T>, u: Vertex<T>) {
+/// Whether `v` and `u` are in the same tree.
+pub fn connected<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> bool {
+    stat_op!(Expose);
+    let root = expose(v);
+    // if `u` is in the same tree, exposing it moves `root` below the new root
+    let other = expose(u);
+    root == other || root.parent().is_some()
+}
+
+pub(crate) fn soft_expose<T: Cluster>(v: Vertex<T>, u: Vertex<T>) {
     unsafe {
         let mut root = expose(v);
         if v.handle() == u.handle() {
@@ -0,0 +1,309 @@
+//! C ABI over `Forest` for the built-in clusters.
+//! Forests are opaque handles, vertices are the integer ids of `Forest`,
+//! every function returns a status code and writes its answers through out pointers.
+//! `include/toptree.h` is generated from this file by `build.rs`.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use crate::node::*;
+use crate::forest::*;
+use crate::query::diameter::*;
+use crate::query::center::*;
+use crate::query::median::*;
+
+pub const TOPTREE_OK: i32 = 0;
+pub const TOPTREE_NULL_POINTER: i32 = 1;
+pub const TOPTREE_UNKNOWN_VERTEX: i32 = 2;
+pub const TOPTREE_SAME_VERTEX: i32 = 3;
+pub const TOPTREE_ALREADY_CONNECTED: i32 = 4;
+pub const TOPTREE_NOT_CONNECTED: i32 = 5;
+pub const TOPTREE_NO_SUCH_EDGE: i32 = 6;
+pub const TOPTREE_NOT_ISOLATED: i32 = 7;
+pub const TOPTREE_UNSUPPORTED: i32 = 8;
+pub const TOPTREE_OUT_OF_RANGE: i32 = 9;
+pub const TOPTREE_INTERNAL_ERROR: i32 = 10;
+pub const TOPTREE_EMPTY: i32 = 11;
+
+pub const TOPTREE_CLUSTER_LENGTH: u32 = 0;
+pub const TOPTREE_CLUSTER_DIAMETER: u32 = 1;
+pub const TOPTREE_CLUSTER_CENTER: u32 = 2;
+pub const TOPTREE_CLUSTER_MEDIAN: u32 = 3;
+
+type Status<T> = Result<T, i32>;
+
+fn status(e: ForestError) -> i32 {
+    match e {
+        ForestError::UnknownVertex(_) => TOPTREE_UNKNOWN_VERTEX,
+        ForestError::SameVertex(_) => TOPTREE_SAME_VERTEX,
+        ForestError::AlreadyConnected(..) => TOPTREE_ALREADY_CONNECTED,
+        ForestError::NotConnected(..) => TOPTREE_NOT_CONNECTED,
+        ForestError::NoSuchEdge(..) => TOPTREE_NO_SUCH_EDGE,
+        ForestError::NotIsolated(_) => TOPTREE_NOT_ISOLATED,
+        ForestError::Empty => TOPTREE_EMPTY,
+    }
+}
+
+fn to_usize(x: u64) -> Status<usize> {
+    if x > usize::MAX as u64 { Err(TOPTREE_OUT_OF_RANGE) } else { Ok(x as usize) }
+}
+
+/// A built-in cluster with `u64` weights and vertex values.
+trait FfiCluster: Cluster {
+    fn from_weight(w: u64) -> Self;
+    fn value(x: u64) -> Status<Self::V>;
+    fn length(&self) -> u64;
+    fn diameter(_v: Vertex<Self>) -> Status<(Vertex<Self>, Vertex<Self>, u64)> { Err(TOPTREE_UNSUPPORTED) }
+    fn center(_v: Vertex<Self>) -> Status<(Vertex<Self>, u64)> { Err(TOPTREE_UNSUPPORTED) }
+    fn median(_v: Vertex<Self>) -> Status<(Vertex<Self>, u64)> { Err(TOPTREE_UNSUPPORTED) }
+}
+
+// the length, diameter and center clusters do not read the vertex values
+impl FfiCluster for u64 {
+    fn from_weight(w: u64) -> Self { w }
+    fn value(x: u64) -> Status<usize> { to_usize(x) }
+    fn length(&self) -> u64 { *self }
+}
+
+impl FfiCluster for Diameter<u64> {
+    fn from_weight(w: u64) -> Self { Diameter::new(w) }
+    fn value(x: u64) -> Status<usize> { to_usize(x) }
+    fn length(&self) -> u64 { self.length }
+    fn diameter(v: Vertex<Self>) -> Status<(Vertex<Self>, Vertex<Self>, u64)> { Ok(diameter(v)) }
+}
+
+impl FfiCluster for Center<u64> {
+    fn from_weight(w: u64) -> Self { Center::new(w) }
+    fn value(x: u64) -> Status<usize> { to_usize(x) }
+    fn length(&self) -> u64 { self.length }
+    fn center(v: Vertex<Self>) -> Status<(Vertex<Self>, u64)> { Ok(center(v)) }
+}
+
+impl FfiCluster for Median<u64, u64> {
+    fn from_weight(w: u64) -> Self { Median::new(w) }
+    fn value(x: u64) -> Status<u64> { Ok(x) }
+    fn length(&self) -> u64 { self.length }
+    fn median(v: Vertex<Self>) -> Status<(Vertex<Self>, u64)> { Ok(weighted_median(v)) }
+}
+
+/// `Forest` with the cluster type erased.
+trait AnyForest {
+    fn add_vertex(&mut self, value: u64) -> Status<usize>;
+    fn link(&mut self, u: usize, v: usize, w: u64) -> Status<()>;
+    fn cut(&mut self, u: usize, v: usize) -> Status<()>;
+    fn set_edge(&mut self, u: usize, v: usize, w: u64) -> Status<()>;
+    fn set_vertex(&mut self, v: usize, value: u64) -> Status<()>;
+    fn connected(&self, u: usize, v: usize) -> Status<bool>;
+    fn path_sum(&self, u: usize, v: usize) -> Status<u64>;
+    fn diameter(&self, v: usize) -> Status<(usize, usize, u64)>;
+    fn center(&self, v: usize) -> Status<(usize, u64)>;
+    fn median(&self, v: usize) -> Status<(usize, u64)>;
+}
+
+impl<T: FfiCluster> AnyForest for Forest<T> {
+    fn add_vertex(&mut self, value: u64) -> Status<usize> {
+        Ok(Forest::add_vertex(self, T::value(value)?))
+    }
+    fn link(&mut self, u: usize, v: usize, w: u64) -> Status<()> {
+        Forest::link(self, u, v, T::from_weight(w)).map_err(status)
+    }
+    fn cut(&mut self, u: usize, v: usize) -> Status<()> {
+        Forest::cut(self, u, v).map(|_| ()).map_err(status)
+    }
+    fn set_edge(&mut self, u: usize, v: usize, w: u64) -> Status<()> {
+        Forest::set_edge(self, u, v, T::from_weight(w)).map_err(status)
+    }
+    fn set_vertex(&mut self, v: usize, value: u64) -> Status<()> {
+        Forest::set_vertex(self, v, T::value(value)?).map_err(status)
+    }
+    fn connected(&self, u: usize, v: usize) -> Status<bool> {
+        Forest::connected(self, u, v).map_err(status)
+    }
+    fn path_sum(&self, u: usize, v: usize) -> Status<u64> {
+        Forest::path(self, u, v).map(|p| p.length()).map_err(status)
+    }
+    fn diameter(&self, v: usize) -> Status<(usize, usize, u64)> {
+        let (x, y, d) = T::diameter(handle(self, v)?)?;
+        Ok((self.id(x).unwrap(), self.id(y).unwrap(), d))
+    }
+    fn center(&self, v: usize) -> Status<(usize, u64)> {
+        let (c, r) = T::center(handle(self, v)?)?;
+        Ok((self.id(c).unwrap(), r))
+    }
+    fn median(&self, v: usize) -> Status<(usize, u64)> {
+        let (m, cost) = T::median(handle(self, v)?)?;
+        Ok((self.id(m).unwrap(), cost))
+    }
+}
+
+// the handles only live for one query while the forest is borrowed
+fn handle<T: FfiCluster>(forest: &Forest<T>, v: usize) -> Status<Vertex<T>> {
+    unsafe { forest.vertex(v).map_err(status) }
+}
+
+pub struct ToptreeForest {
+    forest: Box<dyn AnyForest>,
+    // set when a call panicked and may have left the forest half updated
+    poisoned: bool,
+}
+
+// runs `f` on the forest behind `forest`, turning null pointers and panics into
+// status codes; after a panic the forest is poisoned and only `toptree_forest_free` works
+unsafe fn with_forest<F: FnOnce(&mut dyn AnyForest) -> Status<()>>(forest: *mut ToptreeForest, f: F) -> i32 {
+    let forest = match forest.as_mut() {
+        Some(forest) => forest,
+        None => return TOPTREE_NULL_POINTER,
+    };
+    if forest.poisoned {
+        return TOPTREE_INTERNAL_ERROR;
+    }
+    match catch_unwind(AssertUnwindSafe(|| f(&mut *forest.forest))) {
+        Ok(Ok(())) => TOPTREE_OK,
+        Ok(Err(code)) => code,
+        Err(_) => {
+            forest.poisoned = true;
+            TOPTREE_INTERNAL_ERROR
+        }
+    }
+}
+
+unsafe fn write<T>(out: *mut T, val: T) -> Status<()> {
+    match out.as_mut() {
+        Some(out) => { *out = val; Ok(()) }
+        None => Err(TOPTREE_NULL_POINTER),
+    }
+}
+
+/// Creates an empty forest of the given `TOPTREE_CLUSTER_*` kind. Returns NULL for an unknown kind.
+/// Weights, vertex values and answers are `u64`; sums saturate at `UINT64_MAX`.
+/// Once a call returns `TOPTREE_INTERNAL_ERROR` the forest may be broken, and
+/// every later call on it returns `TOPTREE_INTERNAL_ERROR` until it is freed.
+#[no_mangle]
+pub extern "C" fn toptree_forest_new(kind: u32) -> *mut ToptreeForest {
+    let forest: Box<dyn AnyForest> = match kind {
+        TOPTREE_CLUSTER_LENGTH => Box::new(Forest::<u64>::new()),
+        TOPTREE_CLUSTER_DIAMETER => Box::new(Forest::<Diameter<u64>>::new()),
+        TOPTREE_CLUSTER_CENTER => Box::new(Forest::<Center<u64>>::new()),
+        TOPTREE_CLUSTER_MEDIAN => Box::new(Forest::<Median<u64, u64>>::new()),
+        _ => return std::ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(ToptreeForest { forest, poisoned: false }))
+}
+
+/// Frees a forest and all of its vertices. NULL is ignored.
+///
+/// # Safety
+/// `forest` must be NULL or come from `toptree_forest_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_forest_free(forest: *mut ToptreeForest) {
+    if !forest.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(forest))));
+    }
+}
+
+/// Adds a vertex with the given value and writes its id to `out_id`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and `out_id` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_add_vertex(forest: *mut ToptreeForest, value: u64, out_id: *mut u64) -> i32 {
+    if out_id.is_null() {
+        return TOPTREE_NULL_POINTER;
+    }
+    with_forest(forest, |f| write(out_id, f.add_vertex(value)? as u64))
+}
+
+/// Adds the edge `u`-`v` with weight `w`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new`.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_link(forest: *mut ToptreeForest, u: u64, v: u64, w: u64) -> i32 {
+    with_forest(forest, |f| f.link(to_usize(u)?, to_usize(v)?, w))
+}
+
+/// Removes the edge `u`-`v`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new`.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_cut(forest: *mut ToptreeForest, u: u64, v: u64) -> i32 {
+    with_forest(forest, |f| f.cut(to_usize(u)?, to_usize(v)?))
+}
+
+/// Changes the weight of the edge `u`-`v` to `w`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new`.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_set_edge(forest: *mut ToptreeForest, u: u64, v: u64, w: u64) -> i32 {
+    with_forest(forest, |f| f.set_edge(to_usize(u)?, to_usize(v)?, w))
+}
+
+/// Changes the value of vertex `v`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new`.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_set_vertex(forest: *mut ToptreeForest, v: u64, value: u64) -> i32 {
+    with_forest(forest, |f| f.set_vertex(to_usize(v)?, value))
+}
+
+/// Writes 1 to `out` if `u` and `v` are in the same tree, 0 otherwise.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_connected(forest: *mut ToptreeForest, u: u64, v: u64, out: *mut i32) -> i32 {
+    with_forest(forest, |f| write(out, f.connected(to_usize(u)?, to_usize(v)?)? as i32))
+}
+
+/// Writes the sum of the edge weights on the path `u`..`v` to `out_sum`.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and `out_sum` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_path_sum(forest: *mut ToptreeForest, u: u64, v: u64, out_sum: *mut u64) -> i32 {
+    with_forest(forest, |f| write(out_sum, f.path_sum(to_usize(u)?, to_usize(v)?)?))
+}
+
+/// Writes the endpoints and the length of a diameter of the tree containing `v`.
+/// Needs a `TOPTREE_CLUSTER_DIAMETER` forest.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_diameter(forest: *mut ToptreeForest, v: u64, out_u: *mut u64, out_v: *mut u64, out_length: *mut u64) -> i32 {
+    with_forest(forest, |f| {
+        let (x, y, d) = f.diameter(to_usize(v)?)?;
+        write(out_u, x as u64)?;
+        write(out_v, y as u64)?;
+        write(out_length, d)
+    })
+}
+
+/// Writes a center of the tree containing `v` and its radius.
+/// Needs a `TOPTREE_CLUSTER_CENTER` forest.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_center(forest: *mut ToptreeForest, v: u64, out_center: *mut u64, out_radius: *mut u64) -> i32 {
+    with_forest(forest, |f| {
+        let (c, r) = f.center(to_usize(v)?)?;
+        write(out_center, c as u64)?;
+        write(out_radius, r)
+    })
+}
+
+/// Writes the vertex minimizing the sum of value * distance over the tree containing `v`, and that sum.
+/// Needs a `TOPTREE_CLUSTER_MEDIAN` forest.
+///
+/// # Safety
+/// `forest` must come from `toptree_forest_new` and the out pointers must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn toptree_median(forest: *mut ToptreeForest, v: u64, out_median: *mut u64, out_cost: *mut u64) -> i32 {
+    with_forest(forest, |f| {
+        let (m, cost) = f.median(to_usize(v)?)?;
+        write(out_median, m as u64)?;
+        write(out_cost, cost)
+    })
+}
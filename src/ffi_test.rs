@@ -0,0 +1,56 @@
+//! Drives the C ABI of `toptree::ffi` the way a C caller would.
+
+use toptree::ffi::*;
+
+pub fn ffi_test() {
+    println!("ffi");
+    unsafe {
+        let f = toptree_forest_new(TOPTREE_CLUSTER_DIAMETER);
+        let mut id = 0;
+        for _ in 0..5 {
+            assert!(toptree_add_vertex(f, 0, &mut id) == TOPTREE_OK);
+        }
+        assert!(id == 4);
+        assert!(toptree_link(f, 0, 1, 1) == TOPTREE_OK);
+        assert!(toptree_link(f, 1, 2, 10) == TOPTREE_OK);
+        assert!(toptree_link(f, 1, 3, 3) == TOPTREE_OK);
+        assert!(toptree_link(f, 0, 4, 5) == TOPTREE_OK);
+        assert!(toptree_link(f, 2, 4, 5) == TOPTREE_ALREADY_CONNECTED);
+        let (mut a, mut b, mut d) = (0, 0, 0);
+        assert!(toptree_path_sum(f, 2, 4, &mut d) == TOPTREE_OK && d == 16);
+        assert!(toptree_diameter(f, 3, &mut a, &mut b, &mut d) == TOPTREE_OK && d == 16);
+        assert!(toptree_center(f, 3, &mut a, &mut b) == TOPTREE_UNSUPPORTED);
+        assert!(toptree_cut(f, 0, 2) == TOPTREE_NO_SUCH_EDGE);
+        assert!(toptree_cut(f, 0, 1) == TOPTREE_OK);
+        let mut c = 1;
+        assert!(toptree_connected(f, 2, 4, &mut c) == TOPTREE_OK && c == 0);
+        assert!(toptree_path_sum(f, 2, 4, &mut d) == TOPTREE_NOT_CONNECTED);
+        assert!(toptree_path_sum(f, 2, 99, &mut d) == TOPTREE_UNKNOWN_VERTEX);
+        assert!(toptree_path_sum(f, 2, 3, std::ptr::null_mut()) == TOPTREE_NULL_POINTER);
+        assert!(toptree_path_sum(std::ptr::null_mut(), 2, 3, &mut d) == TOPTREE_NULL_POINTER);
+        toptree_forest_free(f);
+
+        let f = toptree_forest_new(TOPTREE_CLUSTER_MEDIAN);
+        for w in [1, 1, 10].iter() {
+            toptree_add_vertex(f, *w, &mut id);
+        }
+        toptree_link(f, 0, 1, 2);
+        toptree_link(f, 1, 2, 3);
+        assert!(toptree_median(f, 0, &mut a, &mut d) == TOPTREE_OK && a == 2 && d == 8);
+        assert!(toptree_set_vertex(f, 0, 100) == TOPTREE_OK);
+        assert!(toptree_median(f, 0, &mut a, &mut d) == TOPTREE_OK && a == 0);
+        toptree_forest_free(f);
+
+        // weights are u64 whatever the width of usize, and sums saturate
+        let f = toptree_forest_new(TOPTREE_CLUSTER_LENGTH);
+        for _ in 0..3 {
+            toptree_add_vertex(f, 0, &mut id);
+        }
+        assert!(toptree_link(f, 0, 1, u64::MAX - 1) == TOPTREE_OK);
+        assert!(toptree_link(f, 1, 2, 5) == TOPTREE_OK);
+        assert!(toptree_path_sum(f, 0, 1, &mut d) == TOPTREE_OK && d == u64::MAX - 1);
+        assert!(toptree_path_sum(f, 0, 2, &mut d) == TOPTREE_OK && d == u64::MAX);
+        toptree_forest_free(f);
+        assert!(toptree_forest_new(42).is_null());
+    }
+}
@@ -0,0 +1,64 @@
+use crate::node::*;
+use crate::expose::*;
+use crate::path_query::find_edge;
+
+/// The fold of the subtree hanging from `v` when the tree is rooted at its
+/// neighbour `p`, with `v` at `endpoints(0)`.
+///
+/// Panics if `v` and `p` are not adjacent.
+pub fn subtree_fold<T: Cluster>(v: Vertex<T>, p: Vertex<T>) -> T {
+    stat_op!(Fold);
+    assert!(find_edge(v, p).is_some(), "subtree_fold: the vertices are not adjacent");
+    unsafe {
+        soft_expose(v, p);
+        let mut root = v.handle().unwrap();
+        root.push();
+        if let CompNode::Node(n) = root {
+            let left = n.as_ref().child(0);
+            assert!(left.endpoints(1) == v);
+            let mut fold = match n.as_ref().rake() {
+                Some(r) => raked(left.fold(), r.fold(), left.endpoints(0).value_ref(), r.endpoints(0).value_ref(), v.value_ref()),
+                None => left.fold().clone(),
+            };
+            fold.reverse();
+            fold
+        }
+        else { unreachable!() }
+    }
+}
+
+/// The fold of the whole tree containing `v`, with `v` at `endpoints(0)`.
+pub fn component_fold<T: Cluster>(v: Vertex<T>) -> T {
+    stat_op!(Fold);
+    root_fold(expose(v), v)
+}
+
+// `v` must be the middle vertex or an endpoint of the root cluster `root`
+pub(crate) fn root_fold<T: Cluster>(mut root: CompNode<T>, v: Vertex<T>) -> T {
+    unsafe {
+        root.push();
+        match root {
+            CompNode::Node(n) if n.as_ref().child(0).endpoints(1) == v => {
+                let left = n.as_ref().child(0);
+                let right = n.as_ref().child(1);
+                let mut rfold = right.fold().clone();
+                rfold.reverse();
+                let far = right.endpoints(1);
+                let mut fold = raked(&rfold, left.fold(), far.value_ref(), left.endpoints(0).value_ref(), v.value_ref());
+                if let Some(r) = n.as_ref().rake() {
+                    T::rake(&fold, r.fold(), far.value_ref(), r.endpoints(0).value_ref(), v.value_ref(), &mut rfold);
+                    fold = rfold;
+                }
+                fold.reverse();
+                fold
+            }
+            _ => {
+                let mut fold = root.fold().clone();
+                if root.endpoints(0) != v {
+                    fold.reverse();
+                }
+                fold
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ptr::NonNull;
+use std::fmt;
+use crate::node::*;
+use crate::expose::*;
+use crate::link::*;
+use crate::cut::*;
+use crate::path_query::*;
+use crate::fold::*;
+use crate::update::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForestError {
+    UnknownVertex(usize),
+    SameVertex(usize),
+    AlreadyConnected(usize, usize),
+    NotConnected(usize, usize),
+    NoSuchEdge(usize, usize),
+    NotIsolated(usize),
+    Empty,
+}
+
+impl fmt::Display for ForestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ForestError::UnknownVertex(v) => write!(f, "unknown vertex {}", v),
+            ForestError::SameVertex(v) => write!(f, "both endpoints are vertex {}", v),
+            ForestError::AlreadyConnected(u, v) => write!(f, "vertices {} and {} are already connected", u, v),
+            ForestError::NotConnected(u, v) => write!(f, "vertices {} and {} are not connected", u, v),
+            ForestError::NoSuchEdge(u, v) => write!(f, "no edge between {} and {}", u, v),
+            ForestError::NotIsolated(v) => write!(f, "vertex {} still has edges", v),
+            ForestError::Empty => write!(f, "the forest has no vertices"),
+        }
+    }
+}
+
+impl std::error::Error for ForestError {}
+
+/// Owns the vertices of a forest and names them by integer ids `0, 1, ...`
+/// in creation order. Unlike the free functions, every operation checks
+/// its preconditions and reports a `ForestError` instead of corrupting the tree.
+pub struct Forest<T: Cluster> {
+    vertices: Vec<Vertex<T>>,
+    ids: HashMap<Vertex<T>, usize>,
+    // keyed by the sorted endpoints
+    edges: HashMap<(usize, usize), LinkedEdge<T>>,
+    adj: Vec<BTreeSet<usize>>,
+}
+
+// the endpoint the edge was linked from, and the edge
+type LinkedEdge<T> = (usize, NonNull<Edge<T>>);
+
+fn key(u: usize, v: usize) -> (usize, usize) {
+    (std::cmp::min(u, v), std::cmp::max(u, v))
+}
+
+impl<T: Cluster> Default for Forest<T> {
+    fn default() -> Self {
+        Forest::new()
+    }
+}
+
+impl<T: Cluster> Drop for Forest<T> {
+    fn drop(&mut self) {
+        let mut roots = HashSet::new();
+        for v in self.vertices.iter() {
+            roots.insert(expose(*v));
+        }
+        // a later expose in the same tree may have moved an earlier root down
+        roots.retain(|r| r.parent().is_none());
+        unsafe {
+            for root in roots {
+                free_cluster_tree(root);
+            }
+            for v in self.vertices.drain(..) {
+                v.free();
+            }
+        }
+    }
+}
+
+impl<T: Cluster> Forest<T> {
+    pub fn new() -> Self {
+        Forest { vertices: Vec::new(), ids: HashMap::new(), edges: HashMap::new(), adj: Vec::new() }
+    }
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+    pub fn add_vertex(&mut self, val: T::V) -> usize {
+        let v = Vertex::new(val);
+        self.ids.insert(v, self.vertices.len());
+        self.vertices.push(v);
+        self.adj.push(BTreeSet::new());
+        self.vertices.len() - 1
+    }
+    /// Removes the most recently added vertex, which must have no edges.
+    /// Fails with `Empty` if there is no vertex left.
+    pub fn pop_vertex(&mut self) -> Result<usize, ForestError> {
+        let id = self.vertices.len().checked_sub(1).ok_or(ForestError::Empty)?;
+        let v = self.vertices[id];
+        match v.handle() {
+            Some(h @ CompNode::Leaf(_)) if h.parent().is_none() => {}
+            _ => return Err(ForestError::NotIsolated(id)),
+        }
+        self.vertices.pop();
+        self.adj.pop();
+        self.ids.remove(&v);
+        unsafe {
+            free_cluster_tree(v.handle().unwrap());
+            v.free();
+        }
+        Ok(id)
+    }
+    pub(crate) fn get(&self, id: usize) -> Result<Vertex<T>, ForestError> {
+        self.vertices.get(id).cloned().ok_or(ForestError::UnknownVertex(id))
+    }
+    /// The handle of vertex `id`, for the free query functions.
+    ///
+    /// # Safety
+    ///
+    /// The handle must not be used after the vertex is popped or the forest
+    /// is dropped, and must not be linked to or cut from outside the forest.
+    pub unsafe fn vertex(&self, id: usize) -> Result<Vertex<T>, ForestError> {
+        self.get(id)
+    }
+    pub fn id(&self, v: Vertex<T>) -> Option<usize> {
+        self.ids.get(&v).cloned()
+    }
+    pub fn value(&self, v: usize) -> Result<T::V, ForestError> {
+        Ok(self.get(v)?.value())
+    }
+    pub fn connected(&self, u: usize, v: usize) -> Result<bool, ForestError> {
+        let (x, y) = (self.get(u)?, self.get(v)?);
+        Ok(u == v || connected(x, y))
+    }
+    fn edge(&self, u: usize, v: usize) -> Result<LinkedEdge<T>, ForestError> {
+        self.get(u)?;
+        self.get(v)?;
+        self.edges.get(&key(u, v)).cloned().ok_or(ForestError::NoSuchEdge(u, v))
+    }
+    pub fn has_edge(&self, u: usize, v: usize) -> bool {
+        self.edges.contains_key(&key(u, v))
+    }
+    /// The weight of the edge `u`-`v` oriented from `u` to `v`, if there is one.
+    pub fn edge_value(&self, u: usize, v: usize) -> Option<T> {
+        let (from, e) = self.edges.get(&key(u, v))?;
+        let mut w = unsafe { e.as_ref().weight().clone() };
+        if *from != u { w.reverse(); }
+        Some(w)
+    }
+    /// The neighbours of `v` in increasing order, with the weights oriented away from `v`.
+    pub fn edges_of(&self, v: usize) -> Result<Vec<(usize, T)>, ForestError> {
+        self.get(v)?;
+        Ok(self.adj[v].iter().map(|&u| (u, self.edge_value(v, u).unwrap())).collect())
+    }
+    pub fn link(&mut self, u: usize, v: usize, weight: T) -> Result<(), ForestError> {
+        if u == v {
+            return Err(ForestError::SameVertex(u));
+        }
+        if self.connected(u, v)? {
+            return Err(ForestError::AlreadyConnected(u, v));
+        }
+        let e = link_raw(self.vertices[u], self.vertices[v], |_| weight);
+        self.edges.insert(key(u, v), (u, e));
+        self.adj[u].insert(v);
+        self.adj[v].insert(u);
+        Ok(())
+    }
+    /// Removes the edge `u`-`v`. Returns its weight oriented from `u` to `v`
+    /// and the `component` folds of `u` and `v` after the split.
+    pub fn cut(&mut self, u: usize, v: usize) -> Result<(T, T, T), ForestError> {
+        self.edge(u, v)?;
+        let res = cut_raw(self.vertices[u], self.vertices[v]);
+        self.edges.remove(&key(u, v));
+        self.adj[u].remove(&v);
+        self.adj[v].remove(&u);
+        Ok(res)
+    }
+    pub fn path(&self, u: usize, v: usize) -> Result<T, ForestError> {
+        if u == v {
+            self.get(u)?;
+            return Ok(T::identity());
+        }
+        if !self.connected(u, v)? {
+            return Err(ForestError::NotConnected(u, v));
+        }
+        Ok(path_query(self.vertices[u], self.vertices[v]))
+    }
+    /// Like `path`, with the values of `u` and `v` folded in as `path_query_inclusive` does.
+    pub fn path_inclusive(&self, u: usize, v: usize) -> Result<T, ForestError> {
+        if !self.connected(u, v)? {
+            return Err(ForestError::NotConnected(u, v));
+        }
+        Ok(path_query_inclusive(self.vertices[u], self.vertices[v]))
+    }
+    /// Replaces the weight of the edge `u`-`v`, given oriented from `u` to `v`.
+    pub fn set_edge(&mut self, u: usize, v: usize, mut weight: T) -> Result<(), ForestError> {
+        let (from, e) = self.edge(u, v)?;
+        if from != u { weight.reverse(); }
+        update_edge_raw(e, weight);
+        Ok(())
+    }
+    pub fn set_vertex(&mut self, v: usize, val: T::V) -> Result<(), ForestError> {
+        update_vertex(self.get(v)?, val);
+        Ok(())
+    }
+    /// The fold of the whole tree containing `v`, with `v` at `endpoints(0)`.
+    pub fn component(&self, v: usize) -> Result<T, ForestError> {
+        Ok(component_fold(self.get(v)?))
+    }
+    /// The fold of the subtree hanging from `v` when the tree is rooted at its
+    /// neighbour `p`, with `v` at `endpoints(0)`.
+    pub fn subtree(&self, v: usize, p: usize) -> Result<T, ForestError> {
+        self.edge(v, p)?;
+        Ok(subtree_fold(self.vertices[v], self.vertices[p]))
+    }
+}
@@ -0,0 +1,330 @@
+use std::fmt::Debug;
+use crate::node::*;
+use crate::forest::*;
+use crate::path_query::*;
+use crate::fold::*;
+use crate::select::*;
+use crate::update::*;
+use crate::query::path_monoid::Monoid;
+
+#[derive(Clone, Debug)]
+struct LctValue<M> {
+    val: M,
+    root: bool,
+}
+
+impl<M: Monoid> Default for LctValue<M> {
+    fn default() -> Self {
+        LctValue { val: M::identity(), root: false }
+    }
+}
+
+// `sub` folds the inner vertices in no particular order, `marks` counts the roots among them
+#[derive(Clone, Debug)]
+struct LctCluster<M> {
+    fwd: M,
+    bwd: M,
+    sub: M,
+    marks: usize,
+    length: usize,
+}
+
+impl<M: Monoid + Debug> LctCluster<M> {
+    fn edge() -> Self {
+        LctCluster { length: 1, ..Self::identity() }
+    }
+}
+
+impl<M: Monoid + Debug> Cluster for LctCluster<M> {
+    type V = LctValue<M>;
+    fn identity() -> Self {
+        LctCluster { fwd: M::identity(), bwd: M::identity(), sub: M::identity(), marks: 0, length: 0 }
+    }
+    fn compress(a: &Self, b: &Self, _: &LctValue<M>, _: &LctValue<M>, c: &LctValue<M>, out: &mut Self) {
+        *out = LctCluster {
+            fwd: M::op(&M::op(&a.fwd, &c.val), &b.fwd),
+            bwd: M::op(&M::op(&b.bwd, &c.val), &a.bwd),
+            sub: M::op(&M::op(&a.sub, &b.sub), &c.val),
+            marks: a.marks + b.marks + c.root as usize,
+            length: a.length + b.length,
+        };
+    }
+    fn rake(a: &Self, b: &Self, _: &LctValue<M>, bv: &LctValue<M>, _: &LctValue<M>, out: &mut Self) {
+        out.clone_from(a);
+        out.sub = M::op(&M::op(&a.sub, &b.sub), &bv.val);
+        out.marks = a.marks + b.marks + bv.root as usize;
+    }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.fwd, &mut self.bwd);
+    }
+}
+
+/// A rooted forest with values on the vertices, in the vocabulary of link-cut trees.
+/// Vertices are numbered `0, 1, ...` in creation order and every tree has a root,
+/// which is kept as a mark on its vertex value and found by descending the top tree.
+///
+/// Misuse, such as linking a vertex that is not a root, panics. So does
+/// passing a vertex id that was never added, to any method.
+pub struct LinkCutTree<M: Monoid + Debug> {
+    forest: Forest<LctCluster<M>>,
+}
+
+impl<M: Monoid + Debug> Default for LinkCutTree<M> {
+    fn default() -> Self {
+        LinkCutTree::new()
+    }
+}
+
+impl<M: Monoid + Debug> LinkCutTree<M> {
+    pub fn new() -> Self {
+        LinkCutTree { forest: Forest::new() }
+    }
+    /// `n` isolated vertices with value `M::identity()`.
+    pub fn with_len(n: usize) -> Self {
+        let mut lct = LinkCutTree::new();
+        for _ in 0..n {
+            lct.add_vertex(M::identity());
+        }
+        lct
+    }
+    pub fn len(&self) -> usize {
+        self.forest.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.forest.is_empty()
+    }
+    /// Adds an isolated vertex, which is the root of its own tree.
+    pub fn add_vertex(&mut self, val: M) -> usize {
+        self.forest.add_vertex(LctValue { val, root: true })
+    }
+    fn get(&self, v: usize) -> Vertex<LctCluster<M>> {
+        self.forest.get(v).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn value(&self, v: usize) -> M {
+        self.get(v).value().val
+    }
+    pub fn set_value(&mut self, v: usize, val: M) {
+        let x = self.get(v);
+        update_vertex(x, LctValue { val, ..x.value() });
+    }
+    fn set_root(&mut self, v: usize, root: bool) {
+        let x = self.get(v);
+        update_vertex(x, LctValue { root, ..x.value() });
+    }
+    pub fn connected(&self, u: usize, v: usize) -> bool {
+        self.forest.connected(u, v).unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// The root of the tree containing `v`.
+    pub fn root(&self, v: usize) -> usize {
+        let x = self.get(v);
+        if x.value().root { return v }
+        let (a, b) = select(x, |l: &LctCluster<M>, r: &LctCluster<M>, a: &LctValue<M>, b: &LctValue<M>, _: &LctValue<M>| {
+            if l.marks > 0 || a.root { 0 }
+            else if r.marks > 0 || b.root { 1 }
+            else { 0 }
+        });
+        let root = if a.value().root { a } else { b };
+        assert!(root.value().root, "every tree has a marked root");
+        self.forest.id(root).unwrap()
+    }
+    /// Makes `v` the root of its tree.
+    pub fn evert(&mut self, v: usize) {
+        let r = self.root(v);
+        if r != v {
+            self.set_root(r, false);
+            self.set_root(v, true);
+        }
+    }
+    /// Hangs the tree rooted at `child` below `parent`.
+    ///
+    /// Panics if `child` is not a root, the two vertices are already connected
+    /// or either does not exist.
+    pub fn link(&mut self, child: usize, parent: usize) {
+        assert!(self.root(child) == child, "link: vertex {} is not a root", child);
+        self.forest.link(child, parent, LctCluster::edge()).unwrap_or_else(|e| panic!("link: {}", e));
+        self.set_root(child, false);
+    }
+    /// Removes the edge from `child` to its parent, making `child` a root.
+    ///
+    /// Panics if `child` is a root or does not exist.
+    pub fn cut(&mut self, child: usize) {
+        let parent = self.parent(child).unwrap_or_else(|| panic!("cut: vertex {} is a root", child));
+        self.forest.cut(child, parent).unwrap();
+        self.set_root(child, true);
+    }
+    /// The number of edges between `u` and `v`, or `None` if they are not connected.
+    pub fn dist(&self, u: usize, v: usize) -> Option<usize> {
+        self.forest.path(u, v).ok().map(|p| p.length)
+    }
+    pub fn depth(&self, v: usize) -> usize {
+        self.dist(v, self.root(v)).unwrap()
+    }
+    pub fn parent(&self, v: usize) -> Option<usize> {
+        let r = self.root(v);
+        if r == v { None } else { self.jump(v, r, 1) }
+    }
+    /// The vertex `k` edges away from `u` on the path to `v`, or `None` if the
+    /// path is shorter or the vertices are not connected.
+    ///
+    /// Panics if either vertex does not exist.
+    pub fn jump(&self, u: usize, v: usize, k: usize) -> Option<usize> {
+        if self.dist(u, v)? < k { return None }
+        if k == 0 { return Some(u) }
+        let mut node = path_node(self.get(u), self.get(v));
+        let mut k = k;
+        let w = loop {
+            node.push();
+            match node {
+                CompNode::Node(n) => unsafe {
+                    let left = n.as_ref().child(0);
+                    let len = left.fold().length;
+                    if k < len { node = left }
+                    else if k == len { break left.endpoints(1) }
+                    else {
+                        k -= len;
+                        node = n.as_ref().child(1);
+                    }
+                }
+                CompNode::Leaf(_) => break node.endpoints(k),
+            }
+        };
+        self.forest.id(w)
+    }
+    pub fn kth_ancestor(&self, v: usize, k: usize) -> Option<usize> {
+        self.jump(v, self.root(v), k)
+    }
+    /// The lowest common ancestor, or `None` if `u` and `v` are in different trees.
+    ///
+    /// Panics if either vertex does not exist.
+    pub fn lca(&self, u: usize, v: usize) -> Option<usize> {
+        let d = self.dist(u, v)?;
+        let r = self.root(u);
+        let (du, dv) = (self.depth(u), self.depth(v));
+        self.jump(r, u, (du + dv - d) / 2)
+    }
+    /// The product of the vertex values on the path from `u` to `v`, both included.
+    ///
+    /// Panics if the vertices are not connected.
+    pub fn path_aggregate(&self, u: usize, v: usize) -> M {
+        assert!(self.connected(u, v), "path_aggregate: the vertices are not connected");
+        path_query_inclusive(self.get(u), self.get(v)).fwd
+    }
+    /// The product of the vertex values in the subtree of `v`, in no particular
+    /// order, so `M` should be commutative.
+    pub fn subtree_aggregate(&self, v: usize) -> M {
+        let x = self.get(v);
+        let sub = match self.parent(v) {
+            Some(p) => subtree_fold(x, self.get(p)).sub,
+            None => component_fold(x).sub,
+        };
+        M::op(&sub, &x.value().val)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Sum(u64);
+
+impl Monoid for Sum {
+    fn identity() -> Self { Sum(0) }
+    fn op(a: &Self, b: &Self) -> Self { Sum(a.0 + b.0) }
+}
+
+// x -> a * x + b, which does not commute
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Affine(u64, u64);
+
+impl Monoid for Affine {
+    fn identity() -> Self { Affine(1, 0) }
+    fn op(f: &Self, g: &Self) -> Self { Affine(g.0 * f.0 % 1_000_003, (g.0 * f.1 + g.1) % 1_000_003) }
+}
+
+pub fn lct_test() {
+    println!("link cut tree");
+    let n = 12;
+    let mut lct = LinkCutTree::<Sum>::new();
+    let mut aff = LinkCutTree::<Affine>::new();
+    for i in 0..n {
+        lct.add_vertex(Sum(i as u64 + 1));
+        aff.add_vertex(Affine(i as u64 + 2, i as u64));
+    }
+    let mut par: Vec<Option<usize>> = vec![None; n];
+    let root = |par: &[Option<usize>], mut v: usize| {
+        while let Some(p) = par[v] { v = p; }
+        v
+    };
+    let ancestors = |par: &[Option<usize>], mut v: usize| {
+        let mut a = vec![v];
+        while let Some(p) = par[v] { v = p; a.push(v); }
+        a
+    };
+    let check = |lct: &LinkCutTree<Sum>, aff: &LinkCutTree<Affine>, par: &[Option<usize>]| {
+        for v in 0..n {
+            assert!(lct.root(v) == root(par, v));
+            assert!(lct.parent(v) == par[v]);
+            assert!(lct.depth(v) == ancestors(par, v).len() - 1);
+            let sub: u64 = (0..n).filter(|&u| ancestors(par, u).contains(&v)).map(|u| u as u64 + 1).sum();
+            assert!(lct.subtree_aggregate(v) == Sum(sub));
+            for u in 0..n {
+                let (au, av) = (ancestors(par, u), ancestors(par, v));
+                if au.last() != av.last() {
+                    assert!(lct.lca(u, v).is_none());
+                    continue;
+                }
+                let l = *au.iter().find(|x| av.contains(x)).unwrap();
+                assert!(lct.lca(u, v) == Some(l));
+                let mut path: Vec<usize> = au.iter().cloned().take_while(|&x| x != l).collect();
+                path.push(l);
+                path.extend(av.iter().cloned().take_while(|&x| x != l).collect::<Vec<_>>().into_iter().rev());
+                for (k, &x) in path.iter().enumerate() {
+                    assert!(lct.jump(u, v, k) == Some(x));
+                }
+                assert!(lct.jump(u, v, path.len()).is_none());
+                assert!(lct.path_aggregate(u, v) == Sum(path.iter().map(|&x| x as u64 + 1).sum()));
+                let f = path.iter().fold(Affine::identity(), |f, &x| Affine::op(&f, &aff.value(x)));
+                assert!(aff.path_aggregate(u, v) == f);
+            }
+        }
+    };
+    let mut seed = 7u64;
+    let mut rand = |m: usize| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (seed >> 33) as usize % m
+    };
+    for step in 0..200 {
+        let (u, v) = (rand(n), rand(n));
+        match rand(3) {
+            0 => {
+                let r = root(&par, u);
+                if r != root(&par, v) {
+                    lct.link(r, v);
+                    aff.link(r, v);
+                    par[r] = Some(v);
+                }
+            }
+            1 => {
+                if par[u].is_some() {
+                    lct.cut(u);
+                    aff.cut(u);
+                    par[u] = None;
+                }
+            }
+            _ => {
+                lct.evert(u);
+                aff.evert(u);
+                let path = ancestors(&par, u);
+                for w in path.windows(2) {
+                    par[w[1]] = Some(w[0]);
+                }
+                par[u] = None;
+            }
+        }
+        if step % 10 == 0 {
+            check(&lct, &aff, &par);
+        }
+    }
+    check(&lct, &aff, &par);
+    lct.set_value(3, Sum(100));
+    assert!(lct.value(3) == Sum(100));
+    let comp: u64 = (0..n).filter(|&u| root(&par, u) == root(&par, 3)).map(|u| if u == 3 { 100 } else { u as u64 + 1 }).sum();
+    assert!(lct.subtree_aggregate(lct.root(3)) == Sum(comp));
+}
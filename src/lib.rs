@@ -0,0 +1,22 @@
+extern crate self as toptree;
+
+#[macro_use]
+pub mod stats;
+pub mod node;
+pub mod weight;
+mod parent_dir;
+mod splay;
+pub mod expose;
+pub mod link;
+pub mod cut;
+pub mod path_query;
+pub mod fold;
+pub mod select;
+pub mod update;
+pub mod raw;
+pub mod forest;
+pub mod lct;
+pub mod ffi;
+pub mod debug;
+
+pub mod query;
@@ -2,15 +2,33 @@ use std::ptr::NonNull;
 use crate::node::*;
 use crate::expose::*;
 
+/// Adds the edge `v`-`u` with `weight` oriented from `v` to `u` and returns its handle.
+///
+/// Panics if `v == u` or the two vertices are already connected.
 pub fn link<T: Cluster>(v: Vertex<T>, u: Vertex<T>, weight: T) -> NonNull<Edge<T>> {
+    link_with(v, u, |_| weight)
+}
+
+/// Like `link`, but the weight is built from the handle of the new edge.
+///
+/// Panics if `v == u` or the two vertices are already connected.
+pub fn link_with<T: Cluster, F: FnOnce(NonNull<Edge<T>>) -> T>(v: Vertex<T>, u: Vertex<T>, f: F) -> NonNull<Edge<T>> {
+    stat_op!(Link);
+    assert!(v != u && !connected(v, u), "link: the vertices are already connected");
+    link_raw(v, u, f)
+}
+
+// `v` and `u` must be in different trees
+pub(crate) fn link_raw<T: Cluster, F: FnOnce(NonNull<Edge<T>>) -> T>(v: Vertex<T>, u: Vertex<T>, f: F) -> NonNull<Edge<T>> {
+    stat_op!(Link);
     unsafe {
         if v.handle().is_none() && u.handle().is_none() {
-            Edge::new(v, u, weight)
+            Edge::new_with(v, u, f)
         }
         else {
             let nnu = u.handle();
             let nnv = v.handle();
-            let mut e = Edge::new(v, u, weight);
+            let mut e = Edge::new_with(v, u, f);
             let mut left = match nnu {
                 None => {
                     CompNode::Leaf(e)
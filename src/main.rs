@@ -1,23 +1,145 @@
-pub mod node;
-pub mod parent_dir;
-pub mod splay;
-pub mod expose;
-pub mod link;
-pub mod cut;
-pub mod path_query;
-pub mod select;
-pub mod debug;
-
-pub mod query;
-
-fn main(){
-    //query::path_length::path_length_test();
-    //query::diameter::diameter_cut_test();
-    //query::center::center_test();
-    //query::median::median_test();
-    //query::median::median_easy();
-    //query::median::median_easy2();
-    //query::farthest_vertex::farthest_test();
-    //query::diameter::diameter_test();
-    query::median::yuki772();
+use std::io::{self, BufReader};
+use toptree::query;
+
+#[macro_use]
+mod cli;
+mod ffi_test;
+
+const USAGE: &str = r#"usage: toptree [--cluster NAME] [SCRIPT]
+       toptree [--cluster NAME] --repl
+       toptree [--cluster NAME] --json
+       toptree --run DRIVER
+
+Reads commands from SCRIPT (or stdin), one per line, and prints the answers:
+  vertex [VALUE]        add a vertex; ids are 0, 1, ... in creation order
+  link U V W            add the edge U-V with weight W
+  cut U V               remove the edge U-V
+  path U V              fold of the path U..V
+  set-edge U V W        change the weight of the edge U-V
+  set-vertex V VALUE    change the value of V
+  diameter V            endpoints and length of the diameter      (diameter)
+  farthest V            farthest vertex from V and its distance   (diameter)
+  center V              center and radius                         (center)
+  bicenter V            center, the other center or -, radius     (center)
+  median V              weighted median and its cost              (median)
+  select_in_subtree V P the edge the center or median of the      (center, median)
+                        subtree of V away from P lies on
+  nearest_marked V      nearest marked vertex and its distance,   (nearest)
+                        - - if there is none
+
+--repl starts an interactive session that also has named vertices,
+`show`, `validate`, `undo` and `history`; type `help` there.
+
+--json reads one JSON request per line from stdin and writes one JSON
+response per line, e.g. {"op":"link","u":3,"v":5,"w":7}. The ops are
+vertex, link, cut, set_edge, set_vertex, value, connected, has_edge,
+edge, edges, path, path_inclusive, component, subtree, pop_vertex,
+mark, unmark and the cluster specific commands above.
+
+Clusters: length (default), diameter, center, median, nearest"#;
+
+fn drivers(name: &str) -> Option<fn()> {
+    let f: fn() = match name {
+        "path_length_test" => query::path_length::path_length_test,
+        "diameter_test" => query::diameter::diameter_test,
+        "diameter_cut_test" => query::diameter::diameter_cut_test,
+        "diameter_endpoints_test" => query::diameter::diameter_endpoints_test,
+        "center_test" => query::center::center_test,
+        "median_test" => query::median::median_test,
+        "weighted_median_test" => query::median::weighted_median_test,
+        "subtree_median_test" => query::median::subtree_median_test,
+        "yuki772" => query::median::yuki772,
+        "farthest_test" => query::farthest_vertex::farthest_test,
+        "farthest_static_test" => query::farthest_vertex::farthest_static_test,
+        "path_max_edge_test" => query::path_max_edge::path_max_edge_test,
+        "climb_test" => query::climb::climb_test,
+        "vertex_path_test" => query::vertex_path::vertex_path_test,
+        "vertex_label_test" => query::vertex_path::vertex_label_test,
+        "subtree_sum_test" => query::subtree_sum::subtree_sum_test,
+        "nearest_marked_test" => query::nearest_marked::nearest_marked_test,
+        "path_monoid_test" => query::path_monoid::path_monoid_test,
+        "select_reentry_test" => toptree::select::select_reentry_test,
+        "lct_test" => toptree::lct::lct_test,
+        "weight_test" => toptree::weight::weight_test,
+        "ffi_test" => ffi_test::ffi_test,
+        #[cfg(feature = "derive")]
+        "product_test" => query::product::product_test,
+        #[cfg(feature = "stats")]
+        "stats_test" => toptree::stats::stats_test,
+        _ => return None,
+    };
+    Some(f)
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}\n\n{}", msg, USAGE);
+    std::process::exit(2);
+}
+
+fn script<T: cli::ClusterCommands>(path: &Option<String>) -> usize {
+    let (stdout, stderr) = (io::stdout(), io::stderr());
+    let (mut out, mut err) = (stdout.lock(), stderr.lock());
+    match path {
+        Some(path) => {
+            let file = std::fs::File::open(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+            cli::script::run::<T, _, _, _>(BufReader::new(file), &mut out, &mut err)
+        }
+        None => {
+            let stdin = io::stdin();
+            cli::script::run::<T, _, _, _>(stdin.lock(), &mut out, &mut err)
+        }
+    }
+}
+
+fn repl<T: cli::ClusterCommands>() -> usize {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    cli::repl::run::<T, _, _>(stdin.lock(), &mut stdout.lock());
+    0
+}
+
+fn json<T: cli::ClusterCommands>() -> usize {
+    let (stdin, stdout) = (io::stdin(), io::stdout());
+    cli::json::run::<T, _, _>(stdin.lock(), &mut stdout.lock());
+    0
+}
+
+enum Mode {
+    Script,
+    Repl,
+    Json,
+}
+
+fn main() {
+    let mut cluster = "length".to_string();
+    let mut path = None;
+    let mut mode = Mode::Script;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cluster" => cluster = args.next().unwrap_or_else(|| fail("--cluster needs a name")),
+            "--run" => {
+                let name = args.next().unwrap_or_else(|| fail("--run needs a driver name"));
+                let f = drivers(&name).unwrap_or_else(|| fail(&format!("unknown driver `{}`", name)));
+                return f();
+            }
+            "--repl" => mode = Mode::Repl,
+            "--json" => mode = Mode::Json,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if arg.starts_with('-') => fail(&format!("unknown option `{}`", arg)),
+            _ => path = Some(arg),
+        }
+    }
+    let errors = match mode {
+        Mode::Script => with_cluster!(cluster.as_str(), script(&path)),
+        Mode::Repl | Mode::Json if path.is_some() => fail("--repl and --json read from stdin and take no script"),
+        Mode::Repl => with_cluster!(cluster.as_str(), repl()),
+        Mode::Json => with_cluster!(cluster.as_str(), json()),
+    }
+        .unwrap_or_else(|| fail(&format!("unknown cluster `{}`, expected one of {}", cluster, cli::CLUSTERS.join(", "))));
+    if errors > 0 {
+        std::process::exit(1);
+    }
 }
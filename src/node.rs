@@ -1,34 +1,107 @@
 use std::ptr::NonNull;
 use crate::parent_dir::*;
-use crate::link::*;
+use crate::link::link_raw;
 //use crate::expose::*;
 
+/// Input it cannot handle is reported as a compile error on the offending
+/// tokens, never as a panic inside the macro.
+///
+/// ```compile_fail
+/// use toptree::node::*;
+/// use toptree::query::center::Center;
+///
+/// #[derive(Clone, Debug, Cluster)]
+/// enum Either {
+///     Left(Center),
+///     Right(Center),
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use toptree_derive::Cluster;
+
+/// Bound added by `#[derive(Cluster)]` so that fields with different vertex
+/// types are reported on the struct instead of inside the generated code.
+///
+#[cfg_attr(feature = "derive", doc = r#"
+```compile_fail,E0277
+use toptree::node::*;
+use toptree::query::center::Center;
+use toptree::query::nearest_marked::NearestMarked;
+
+#[derive(Clone, Debug, Cluster)]
+struct Mixed {
+    center: Center,
+    near: NearestMarked,
+}
+```
+"#)]
+#[doc(hidden)]
+#[diagnostic::on_unimplemented(
+    message = "`#[derive(Cluster)]` needs every field to have the same vertex type, found `{Self}` and `{V}`",
+    label = "the fields of this struct have different vertex types"
+)]
+pub trait SameVertex<V> {
+    fn as_same(&self) -> &V;
+}
+
+impl<V> SameVertex<V> for V {
+    fn as_same(&self) -> &V { self }
+}
+
 pub trait Cluster: Clone {
-    type V: Default + Copy + std::fmt::Debug;
+    type V: Default + Clone + std::fmt::Debug;
     fn identity() -> Self;
-    fn compress(left: Self, right: Self, a: Self::V, b: Self::V, c: Self::V) -> Self;
-    fn rake(left: Self, right: Self, a: Self::V, b: Self::V, c: Self::V) -> Self;
+    /// Writes the fold of `left` followed by `right` into `out`. `out` holds
+    /// a stale cluster whose buffers may be reused and never aliases the inputs.
+    fn compress(left: &Self, right: &Self, a: &Self::V, b: &Self::V, c: &Self::V, out: &mut Self);
+    /// Writes `left` with `right` raked onto it into `out`, like `compress`.
+    fn rake(left: &Self, right: &Self, a: &Self::V, b: &Self::V, c: &Self::V, out: &mut Self);
     fn reverse(&mut self);
+    /// Writes the leaf cluster of an edge with weight `weight` from `a` to `b`
+    /// into `out`, like `compress`. Called whenever the leaf is built, except
+    /// for the edges to dummy vertices, which copy their weight.
+    fn from_edge(weight: &Self, _a: &Self::V, _b: &Self::V, out: &mut Self) { out.clone_from(weight) }
+    /// Whether `update_vertex` has to rebuild every cluster touching the
+    /// vertex instead of only refolding the root: set it when `from_edge`
+    /// reads `a` or `b`, `compress` reads `a` or `b` or `rake` reads `a` or `c`.
+    const UPDATE_VERTEX_REBUILDS_EDGES: bool = false;
 }
 
-pub type Link<N> = Option<N>;
+// `T::compress` into a new cluster
+pub(crate) fn compressed<T: Cluster>(left: &T, right: &T, a: &T::V, b: &T::V, c: &T::V) -> T {
+    let mut out = T::identity();
+    T::compress(left, right, a, b, c, &mut out);
+    out
+}
 
-pub struct VertexRaw<T: Cluster> {
+// `T::rake` into a new cluster
+pub(crate) fn raked<T: Cluster>(left: &T, right: &T, a: &T::V, b: &T::V, c: &T::V) -> T {
+    let mut out = T::identity();
+    T::rake(left, right, a, b, c, &mut out);
+    out
+}
+
+pub(crate) type Link<N> = Option<N>;
+
+pub(crate) struct VertexRaw<T: Cluster> {
     val: T::V,
-    handle: Option<CompNode<T>>
+    handle: Option<CompNode<T>>,
+    dummy: bool,
 }
 
 impl<T: Cluster> VertexRaw<T> {
     pub fn new(val: T::V) -> Self {
         VertexRaw {
-            val: val,
+            val,
             handle: None,
+            dummy: false,
         }
     }
-    pub fn dummy() -> Self {
+    pub(crate) fn dummy() -> Self {
         VertexRaw {
             val: T::V::default(),
             handle: None,
+            dummy: true,
         }
     }
     pub fn handle(&self) -> Option<CompNode<T>> {
@@ -37,47 +110,106 @@ impl<T: Cluster> VertexRaw<T> {
     pub fn handle_mut(&mut self) -> &mut Option<CompNode<T>> {
         &mut self.handle
     }
-    pub fn value(&self) -> T::V {
-        self.val
+    pub fn value(&self) -> &T::V {
+        &self.val
     }
     pub fn value_set(&mut self, val: T::V) {
         self.val = val;
     }
+    pub fn is_dummy(&self) -> bool {
+        self.dummy
+    }
 }
 
+/// A handle to a vertex. Handles are `Copy` and compare by identity.
 pub struct Vertex<T: Cluster> {
     vertex: NonNull<VertexRaw<T>>,
 }
 
 impl<T: Cluster> Vertex<T> {
-    pub fn dangling() -> Self {
+    pub(crate) fn dangling() -> Self {
         Vertex { vertex: NonNull::dangling() }
     }
+    /// A new isolated vertex with value `val`. Its memory is never freed
+    /// unless it is owned by a `Forest`, so the handle stays valid.
     pub fn new(val: T::V) -> Self {
         unsafe {
             let v = Vertex { vertex: NonNull::new_unchecked(Box::into_raw(Box::new(VertexRaw::new(val)))) };
+            stat!(allocs);
             let dummy = Vertex { vertex: NonNull::new_unchecked(Box::into_raw(Box::new(VertexRaw::dummy()))) };
-            link(v, dummy, T::identity());
+            stat!(allocs);
+            link_raw(v, dummy, |_| T::identity());
             v
         }
     }
-    pub fn handle(&self) -> Option<CompNode<T>> {
+    pub(crate) fn handle(&self) -> Option<CompNode<T>> {
         unsafe { self.vertex.as_ref().handle() }
     }
-    pub fn handle_mut(&mut self) -> &mut Option<CompNode<T>> {
+    pub(crate) fn handle_mut(&mut self) -> &mut Option<CompNode<T>> {
         unsafe { self.vertex.as_mut().handle_mut() }
     }
+    /// The value stored at the vertex. Change it with `update_vertex`.
     pub fn value(&self) -> T::V {
+        self.value_ref().clone()
+    }
+    // only valid until the value is next set
+    pub(crate) fn value_ref(&self) -> &T::V {
         unsafe { self.vertex.as_ref().value() }
     }
-    pub fn value_set(&mut self, val: T::V) {
+    // does not refresh the folds above the vertex, see `update_vertex`
+    pub(crate) fn value_set(&mut self, val: T::V) {
         unsafe { self.vertex.as_mut().value_set(val); }
     }
+    /// Whether this is one of the internal vertices hanging off every real vertex.
+    /// `select` may return them as the far end of a leaf.
+    pub fn is_dummy(&self) -> bool {
+        unsafe { self.vertex.as_ref().is_dummy() }
+    }
+    // frees the vertex itself; its tree must already be freed or never touched again
+    pub(crate) unsafe fn free(self) {
+        let _ = Box::from_raw(self.vertex.as_ptr());
+        stat!(frees);
+    }
+}
+
+/// Frees every node of the tree rooted at `root`, together with the dummy vertices.
+/// Real vertices are left to the caller.
+pub(crate) unsafe fn free_cluster_tree<T: Cluster>(root: CompNode<T>) {
+    match root {
+        CompNode::Node(n) => {
+            free_cluster_tree(n.as_ref().child(0));
+            free_cluster_tree(n.as_ref().child(1));
+            if let Some(r) = n.as_ref().rake() {
+                free_rake_tree(r);
+            }
+            let _ = Box::from_raw(n.as_ptr());
+        }
+        CompNode::Leaf(e) => {
+            for dir in 0..2 {
+                let v = e.as_ref().endpoints(dir);
+                if v.is_dummy() { v.free(); }
+            }
+            let _ = Box::from_raw(e.as_ptr());
+        }
+    }
+    stat!(frees);
+}
+
+unsafe fn free_rake_tree<T: Cluster>(root: RakeNode<T>) {
+    match root {
+        RakeNode::Node(r) => {
+            free_rake_tree(r.as_ref().child(0));
+            free_rake_tree(r.as_ref().child(1));
+            let _ = Box::from_raw(r.as_ptr());
+            stat!(frees);
+        }
+        RakeNode::Leaf(c) => free_cluster_tree(c),
+    }
 }
 
 impl<T: Cluster> Clone for Vertex<T> {
     fn clone(&self) -> Self {
-        Vertex { vertex: self.vertex.clone() }
+        *self
     }
 }
 impl<T: Cluster> Copy for Vertex<T> {}
@@ -86,32 +218,42 @@ impl<T: Cluster> PartialEq for Vertex<T> {
         self.vertex == other.vertex
     }
 }
+impl<T: Cluster> Eq for Vertex<T> {}
+impl<T: Cluster> std::hash::Hash for Vertex<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.vertex.hash(state);
+    }
+}
 
-pub enum CompNode<T: Cluster> {
+pub(crate) enum CompNode<T: Cluster> {
     Node(NonNull<Compress<T>>),
     Leaf(NonNull<Edge<T>>),
 }
 
-pub enum RakeNode<T: Cluster> {
+pub(crate) enum RakeNode<T: Cluster> {
     Node(NonNull<Rake<T>>),
     Leaf(CompNode<T>),
 }
 
-pub enum ParentNode<T: Cluster> {
+pub(crate) enum ParentNode<T: Cluster> {
     Compress(NonNull<Compress<T>>),
     Rake(NonNull<Rake<T>>),
 }
 
+/// An edge of the underlying tree. Only its address is public, as the handle
+/// returned by `link`; see the `raw` module for what can be done with it.
 pub struct Edge<T: Cluster> {
     v: [Vertex<T>; 2],
     par: Link<ParentNode<T>>,
     me: NonNull<Edge<T>>,
+    flipped: bool,
+    weight: T,
 
 
-    pub val: T,
+    pub(crate) val: T,
 }
 
-pub struct Compress<T: Cluster> {
+pub(crate) struct Compress<T: Cluster> {
     ch: [CompNode<T>; 2],
     v: [Vertex<T>; 2],
     rake: Link<RakeNode<T>>,
@@ -119,13 +261,15 @@ pub struct Compress<T: Cluster> {
     me: NonNull<Compress<T>>,
     rev: bool,
 
-    pub guard: bool,
+    pub(crate) guard: bool,
 
 
-    pub fold: T
+    pub(crate) fold: T,
+    // the left child with the rake hanging from it, kept to reuse its buffers
+    raked: T,
 }
 
-pub struct Rake<T: Cluster> {
+pub(crate) struct Rake<T: Cluster> {
     ch: [RakeNode<T>; 2],
     v: [Vertex<T>; 2],
     par: Link<ParentNode<T>>,
@@ -134,23 +278,55 @@ pub struct Rake<T: Cluster> {
 }
 
 impl<T: Cluster> Edge<T> {
-    pub fn new(v: Vertex<T>, u: Vertex<T>, val: T) -> NonNull<Edge<T>> {
+    pub(crate) fn new_with<F: FnOnce(NonNull<Edge<T>>) -> T>(v: Vertex<T>, u: Vertex<T>, f: F) -> NonNull<Edge<T>> {
         unsafe {
             let mut e = NonNull::new_unchecked(Box::into_raw(Box::new(Edge {
                 v: [v, u],
                 par: None,
-                val: val,
+                val: T::identity(),
                 me: NonNull::dangling(),
+                flipped: false,
+                weight: T::identity(),
             })));
+            stat!(allocs);
             e.as_mut().me = e;
+            e.as_mut().weight = f(e);
             e.as_mut().fix();
             e
         }
     }
+    pub(crate) fn endpoints(&self, dir: usize) -> Vertex<T> {
+        self.v[dir]
+    }
+    pub(crate) fn weight(&self) -> &T {
+        &self.weight
+    }
+    // the weight oriented away from the endpoint `x`
+    pub(crate) fn weight_from(&self, x: Vertex<T>) -> T {
+        let from = if self.flipped { self.v[1] } else { self.v[0] };
+        let mut weight = self.weight.clone();
+        if from != x { weight.reverse(); }
+        weight
+    }
+    pub(crate) fn weight_set(&mut self, weight: T) {
+        self.weight = weight;
+    }
+    // refolds `val` from `weight`, reusing its buffers
+    fn leaf(&mut self) {
+        // `weight` is kept in the orientation it was linked with
+        let (a, b) = if self.flipped { (self.v[1], self.v[0]) } else { (self.v[0], self.v[1]) };
+        if !a.is_dummy() && !b.is_dummy() {
+            T::from_edge(&self.weight, a.value_ref(), b.value_ref(), &mut self.val);
+        }
+        else {
+            self.val.clone_from(&self.weight);
+        }
+        if self.flipped { self.val.reverse(); }
+    }
 }
 
 impl<T: Cluster> Compress<T> {
-    pub fn new(left: CompNode<T>, right: CompNode<T>) -> NonNull<Compress<T>> {
+    pub(crate) fn new(left: CompNode<T>, right: CompNode<T>) -> NonNull<Compress<T>> {
         unsafe {
             let mut n = NonNull::new_unchecked(Box::into_raw(Box::new(Compress {
                 ch: [left, right],
@@ -161,7 +337,9 @@ impl<T: Cluster> Compress<T> {
                 me: NonNull::dangling(),
                 guard: false,
                 fold: T::identity(),
+                raked: T::identity(),
             })));
+            stat!(allocs);
             n.as_mut().me = n;
             n.as_mut().fix();
             n
@@ -170,7 +348,7 @@ impl<T: Cluster> Compress<T> {
 }
 
 impl<T: Cluster> Rake<T> {
-    pub fn new(left: RakeNode<T>, right: RakeNode<T>) -> NonNull<Rake<T>> {
+    pub(crate) fn new(left: RakeNode<T>, right: RakeNode<T>) -> NonNull<Rake<T>> {
         unsafe {
             let mut r = NonNull::new_unchecked(Box::into_raw(Box::new(Rake {
                 ch: [left, right],
@@ -178,13 +356,14 @@ impl<T: Cluster> Rake<T> {
                 par: None,
                 fold: T::identity()
             })));
+            stat!(allocs);
             r.as_mut().fix();
             r
         }
     }
 }
 
-pub trait TVertex<T: Cluster> {
+pub(crate) trait TVertex<T: Cluster> {
     fn fix(&mut self);
     fn push(&mut self);
     fn reverse(&mut self);
@@ -192,7 +371,7 @@ pub trait TVertex<T: Cluster> {
     fn parent_mut(&mut self) -> &mut Link<ParentNode<T>>;
 }
 
-pub trait Node<T: Cluster>: TVertex<T> {
+pub(crate) trait Node<T: Cluster>: TVertex<T> {
     type Child: TVertex<T>;
     fn child(&self, dir: usize) -> Self::Child;
     fn child_mut(&mut self, dir: usize) -> &mut Self::Child;
@@ -200,6 +379,8 @@ pub trait Node<T: Cluster>: TVertex<T> {
 
 impl<T: Cluster> TVertex<T> for Edge<T> {
     fn fix(&mut self) {
+        stat!(fixes);
+        self.leaf();
         match self.parent() {
             Some(ParentNode::Compress(_)) => {
                 if parent_dir_comp(CompNode::Leaf(self.me)).is_none() {
@@ -219,29 +400,34 @@ impl<T: Cluster> TVertex<T> for Edge<T> {
     fn reverse(&mut self) {
         self.v.swap(0, 1);
         self.val.reverse();
+        self.flipped ^= true;
     }
     fn parent(&self) -> Link<ParentNode<T>> { self.par }
     fn parent_mut(&mut self) -> &mut Link<ParentNode<T>> { &mut self.par }
 }
 
 impl<T: Cluster> Compress<T> {
-    pub fn rake(&self) -> Link<RakeNode<T>> { self.rake }
-    pub fn rake_mut(&mut self) -> &mut Link<RakeNode<T>> { &mut self.rake }
+    pub(crate) fn rake(&self) -> Link<RakeNode<T>> { self.rake }
+    pub(crate) fn rake_mut(&mut self) -> &mut Link<RakeNode<T>> { &mut self.rake }
+    pub(crate) fn rev(&self) -> bool { self.rev }
 }
 
 impl<T: Cluster> TVertex<T> for Compress<T> {
     fn fix(&mut self) {
+        stat!(fixes);
         self.push();
         self.v[0] = self.ch[0].endpoints(0);
         self.v[1] = self.ch[1].endpoints(1);
 
-        self.fold = T::compress(
-            match self.rake {
-                Some(r) => T::rake(self.ch[0].fold(), r.fold(), self.ch[0].endpoints(0).value(), r.endpoints(0).value(), self.ch[0].endpoints(1).value()),
-                None => self.ch[0].fold(),
-            },
-            self.ch[1].fold(), self.ch[0].endpoints(0).value(), self.ch[1].endpoints(1).value(), self.ch[0].endpoints(1).value()
-            );
+        let mid = self.ch[0].endpoints(1);
+        let (a, b, mid) = (self.v[0].value_ref(), self.v[1].value_ref(), mid.value_ref());
+        match self.rake {
+            Some(r) => {
+                T::rake(self.ch[0].fold(), r.fold(), a, r.endpoints(0).value_ref(), mid, &mut self.raked);
+                T::compress(&self.raked, self.ch[1].fold(), a, b, mid, &mut self.fold);
+            }
+            None => T::compress(self.ch[0].fold(), self.ch[1].fold(), a, b, mid, &mut self.fold),
+        }
         *self.ch[0].endpoints(1).handle_mut() = Some(CompNode::Node(self.me));
 
         assert!(self.ch[0].endpoints(1) == self.ch[1].endpoints(0));
@@ -286,9 +472,10 @@ impl<T: Cluster> Node<T> for Compress<T> {
 
 impl<T: Cluster> TVertex<T> for Rake<T> {
     fn fix(&mut self) {
+        stat!(fixes);
         self.push();
         self.v = [self.ch[0].endpoints(0), self.ch[0].endpoints(1)];
-        self.fold = T::rake(self.ch[0].fold(), self.ch[1].fold(), self.ch[0].endpoints(0).value(), self.ch[1].endpoints(0).value(), self.ch[0].endpoints(1).value());
+        T::rake(self.ch[0].fold(), self.ch[1].fold(), self.ch[0].endpoints(0).value_ref(), self.ch[1].endpoints(0).value_ref(), self.ch[0].endpoints(1).value_ref(), &mut self.fold);
     }
     fn push(&mut self) {
     }
@@ -305,7 +492,7 @@ impl<T: Cluster> Node<T> for Rake<T> {
 }
 
 impl<T: Cluster> CompNode<T> {
-    pub fn endpoints(&self, dir: usize) -> Vertex<T> {
+    pub(crate) fn endpoints(&self, dir: usize) -> Vertex<T> {
         unsafe {
             match *self {
                 CompNode::Node(node) => node.as_ref().v[dir],
@@ -313,18 +500,19 @@ impl<T: Cluster> CompNode<T> {
             }
         }
     }
-    pub fn fold(&self) -> T {
+    // only valid until the node is next fixed or reversed
+    pub(crate) fn fold(&self) -> &T {
         unsafe {
             match *self {
-                CompNode::Node(node) => node.as_ref().fold.clone(),
-                CompNode::Leaf(leaf) => leaf.as_ref().val.clone(),
+                CompNode::Node(node) => &(*node.as_ptr()).fold,
+                CompNode::Leaf(leaf) => &(*leaf.as_ptr()).val,
             }
         }
     }
 }
 
 impl<T: Cluster> RakeNode<T> {
-    pub fn endpoints(&self, dir: usize) -> Vertex<T> {
+    pub(crate) fn endpoints(&self, dir: usize) -> Vertex<T> {
         unsafe {
             match *self {
                 RakeNode::Node(node) => node.as_ref().v[dir],
@@ -332,10 +520,10 @@ impl<T: Cluster> RakeNode<T> {
             }
         }
     }
-    pub fn fold(&self) -> T {
+    pub(crate) fn fold(&self) -> &T {
         unsafe {
-            match *self {
-                RakeNode::Node(node) => node.as_ref().fold.clone(),
+            match self {
+                RakeNode::Node(node) => &(*node.as_ptr()).fold,
                 RakeNode::Leaf(leaf) => leaf.fold(),
             }
         }
@@ -480,6 +668,15 @@ impl<T: Cluster> PartialEq for CompNode<T> {
         }
     }
 }
+impl<T: Cluster> Eq for CompNode<T> {}
+impl<T: Cluster> std::hash::Hash for CompNode<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        match *self {
+            CompNode::Node(n) => n.hash(state),
+            CompNode::Leaf(e) => e.hash(state),
+        }
+    }
+}
 
 impl<T: Cluster> PartialEq for RakeNode<T> {
     fn eq(&self, other: &Self) -> bool {
@@ -505,28 +702,19 @@ impl<T: Cluster> PartialEq for ParentNode<T> {
 
 impl<T: Cluster> Clone for CompNode<T> {
     fn clone(&self) -> Self {
-        match *self {
-            CompNode::Node(a) => CompNode::Node(a),
-            CompNode::Leaf(a) => CompNode::Leaf(a),
-        }
+        *self
     }
 }
 
 impl<T: Cluster> Clone for RakeNode<T> {
     fn clone(&self) -> Self {
-        match *self {
-            RakeNode::Node(a) => RakeNode::Node(a),
-            RakeNode::Leaf(a) => RakeNode::Leaf(a),
-        }
+        *self
     }
 }
 
 impl<T: Cluster> Clone for ParentNode<T> {
     fn clone(&self) -> Self {
-        match *self {
-            ParentNode::Compress(a) => ParentNode::Compress(a),
-            ParentNode::Rake(a) => ParentNode::Rake(a),
-        }
+        *self
     }
 }
 
@@ -1,7 +1,7 @@
 use std::ptr::NonNull;
 use crate::node::*;
 
-pub fn parent_dir_comp<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Compress<T>>)> {
+pub(crate) fn parent_dir_comp<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Compress<T>>)> {
     unsafe {
         match child.parent() {
             Some(ParentNode::Compress(p)) => {
@@ -15,7 +15,7 @@ pub fn parent_dir_comp<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull
     }
 }
 
-pub fn parent_dir_comp_guard<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Compress<T>>)> {
+pub(crate) fn parent_dir_comp_guard<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Compress<T>>)> {
     unsafe {
         match child.parent() {
             Some(ParentNode::Compress(p)) => {
@@ -29,7 +29,7 @@ pub fn parent_dir_comp_guard<T: Cluster>(child: CompNode<T>) -> Option<(usize, N
 }
 
 
-pub fn parent_dir_comp_rake<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Rake<T>>)> { 
+pub(crate) fn parent_dir_comp_rake<T: Cluster>(child: CompNode<T>) -> Option<(usize, NonNull<Rake<T>>)> { 
     unsafe { 
         match child.parent() {
             Some(ParentNode::Rake(p)) => {
@@ -43,7 +43,7 @@ pub fn parent_dir_comp_rake<T: Cluster>(child: CompNode<T>) -> Option<(usize, No
 }
 
 
-pub fn parent_dir_rake<T: Cluster>(child: RakeNode<T>) -> Option<(usize, NonNull<Rake<T>>)> {
+pub(crate) fn parent_dir_rake<T: Cluster>(child: RakeNode<T>) -> Option<(usize, NonNull<Rake<T>>)> {
     unsafe {
         match child.parent() {
             Some(ParentNode::Rake(p)) => {
@@ -1,26 +1,28 @@
+use std::ptr::NonNull;
 use crate::node::*;
 use crate::expose::*;
 
-pub fn path_query<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> T {
+// the cluster of the path v..u after soft_expose(v, u)
+pub(crate) fn path_node<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> CompNode<T> {
     unsafe {
         soft_expose(v, u);
         let mut root = v.handle().unwrap();
         root.push();
 
         if root.endpoints(0) == v && root.endpoints(1) == u {
-            root.fold()
+            root
         }
         else if root.endpoints(0) == v {
             if let CompNode::Node(mut n) = root {
                 n.as_mut().push();
-                n.as_ref().child(0).fold()
+                n.as_ref().child(0)
             }
             else { unreachable!() }
         }
         else if root.endpoints(1) == u {
             if let CompNode::Node(mut n) = root {
                 n.as_mut().push();
-                n.as_ref().child(1).fold()
+                n.as_ref().child(1)
             }
             else { unreachable!() }
         }
@@ -29,7 +31,7 @@ pub fn path_query<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> T {
                 n.as_mut().push();
                 if let CompNode::Node(mut n2) = n.as_ref().child(1) {
                     n2.as_mut().push();
-                    n2.as_ref().child(0).fold()
+                    n2.as_ref().child(0)
                 }
                 else { unreachable!() }
             }
@@ -37,3 +39,47 @@ pub fn path_query<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> T {
         }
     }
 }
+
+/// The fold of the path from `v` to `u`, oriented from `v`, without the
+/// values of its end vertices. The empty path of `v == u` folds to `identity`.
+///
+/// Panics if the vertices are not connected.
+pub fn path_query<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> T {
+    stat_op!(PathQuery);
+    if v == u { return T::identity() }
+    assert!(connected(v, u), "path_query: the vertices are not connected");
+    path_node(v, u).fold().clone()
+}
+
+/// The edge `v`-`u`, or `None` if the two vertices are not adjacent.
+pub fn find_edge<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> Option<NonNull<Edge<T>>> {
+    stat_op!(Expose);
+    if v == u || !connected(v, u) { return None }
+    match path_node(v, u) {
+        CompNode::Leaf(e) => Some(e),
+        CompNode::Node(_) => None,
+    }
+}
+
+/// The fold of the path from `v` to `u` including the values of both ends.
+///
+/// Each end `x` is folded in as the middle vertex of a `compress` with the
+/// empty edge `T::from_edge(&T::identity(), x, x)` on the outside, so the
+/// result has `v` and `u` as its boundaries and every `compress` sees the
+/// real vertex values. `identity` must be neutral for `compress`, and the
+/// empty edge must add nothing either, as for the built-in clusters.
+///
+/// Panics if the vertices are not connected.
+pub fn path_query_inclusive<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> T {
+    stat_op!(PathQuery);
+    let (x, y) = (v.value_ref(), u.value_ref());
+    let mut left = T::identity();
+    T::from_edge(&T::identity(), x, x, &mut left);
+    if v == u {
+        return compressed(&left, &left, x, x, x);
+    }
+    let path = path_query(v, u);
+    let mut right = T::identity();
+    T::from_edge(&T::identity(), y, y, &mut right);
+    compressed(&compressed(&left, &path, x, y, x), &right, x, y, y)
+}
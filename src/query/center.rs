@@ -1,20 +1,19 @@
 use crate::node::*;
 use crate::link::*;
 use crate::select::*;
-use crate::expose::*;
+use crate::fold::*;
+use crate::weight::*;
 
 #[derive(Clone, Debug)]
-struct Center {
-    radius: usize,
-    max_dist_left: usize,
-    max_dist_right: usize,
-    length: usize
+pub struct Center<W = usize> {
+    pub max_dist_left: W,
+    pub max_dist_right: W,
+    pub length: W
 }
 
-impl Center {
-    fn new(l: usize) -> Self {
+impl<W: Weight> Center<W> {
+    pub fn new(l: W) -> Self {
         Center {
-            radius: l,
             max_dist_left: l,
             max_dist_right: l,
             length: l,
@@ -22,40 +21,76 @@ impl Center {
     }
 }
 
-impl Cluster for Center {
+impl<W: Weight> Cluster for Center<W> {
     type V = usize;
     fn identity() -> Self {
         Center {
-            radius: 0,
-            max_dist_left: 0,
-            max_dist_right: 0,
-            length: 0,
+            max_dist_left: W::zero(),
+            max_dist_right: W::zero(),
+            length: W::zero(),
         }
     }
-    fn compress(a: Self, b: Self, _: usize, _: usize, _: usize) -> Self {
-        Center {
-            radius: std::cmp::max(a.max_dist_right, b.max_dist_left),
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_left),
-            max_dist_right: std::cmp::max(b.max_dist_right, b.length + a.max_dist_right),
-            length: a.length + b.length
-        }
+    fn compress(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        *out = Center {
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_left)),
+            max_dist_right: std::cmp::max(b.max_dist_right, b.length.weight_add(a.max_dist_right)),
+            length: a.length.weight_add(b.length)
+        };
     }
-    fn rake(a: Self, b: Self, _: usize, _: usize, _: usize) -> Self {
-        Center {
-            radius: 0,
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_right),
+    fn rake(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        *out = Center {
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_right)),
             max_dist_right: std::cmp::max(a.max_dist_right, b.max_dist_right),
             length: a.length,
-        }
+        };
     }
     fn reverse(&mut self) {
         std::mem::swap(&mut self.max_dist_left, &mut self.max_dist_right);
     }
 }
 
+type CenterVertex<W> = Vertex<Center<W>>;
+
+fn eccentricity<W: Weight>(v: CenterVertex<W>) -> W {
+    component_fold(v).max_dist_left
+}
+
+/// The `sel` of `bicenter`: go to the half holding the vertex farthest from
+/// the split vertex. With `select_in_subtree` it finds the center of a subtree
+/// among the two endpoints returned.
+pub fn toward_center<W: Weight>(a: &Center<W>, b: &Center<W>, _: &usize, _: &usize, _: &usize) -> usize {
+    if a.max_dist_right >= b.max_dist_left { 0 }
+    else { 1 }
+}
+
+/// The center of the tree containing `v`, the vertex of smallest eccentricity,
+/// with the other center when two adjacent vertices tie, and the radius.
+/// Dummy vertices are never returned. The edge weights must be non-negative.
+pub fn bicenter<W: Weight>(v: CenterVertex<W>) -> (CenterVertex<W>, Option<CenterVertex<W>>, W) {
+    let (x, y) = select(v, toward_center);
+    if x.is_dummy() {
+        (y, None, eccentricity(y))
+    }
+    else if y.is_dummy() {
+        (x, None, eccentricity(x))
+    }
+    else {
+        let (ex, ey) = (eccentricity(x), eccentricity(y));
+        if ex < ey { (x, None, ex) }
+        else if ey < ex { (y, None, ey) }
+        else { (x, Some(y), ex) }
+    }
+}
+
+/// `bicenter` without the second center.
+pub fn center<W: Weight>(v: CenterVertex<W>) -> (CenterVertex<W>, W) {
+    let (c, _, radius) = bicenter(v);
+    (c, radius)
+}
+
 pub fn center_test() {
     println!("diameter cut");
-    let v: Vec<_> = (0..13).map(|i| Vertex::new(i)).collect();
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
     let edges = [
         (0usize, 1usize, 1usize),
         (1, 2, 10),
@@ -74,10 +109,14 @@ pub fn center_test() {
     for (a, b, w) in edges.iter() {
         es.push(link(v[*a], v[*b], Center::new(*w)));
     }
-    let center = select(v[0], |a, b, _, _, _| {
-        if a.max_dist_right >= b.max_dist_left { 0 }
-        else { 1 }
-    });
-    println!("center vertices {}, {}", center.0.value(), center.1.value());
-    println!("radius {}, {}", expose(center.0).fold().radius, expose(center.1).fold().radius);
+    let (c, radius) = center(v[0]);
+    println!("center vertex {}, radius {}", c.value(), radius);
+    let min_ecc = v.iter().map(|x| eccentricity(*x)).min().unwrap();
+    assert!(radius == min_ecc && eccentricity(c) == min_ecc);
+
+    let single = Vertex::new(13);
+    assert!(center(single) == (single, 0));
+    let pair = [Vertex::new(14), Vertex::new(15)];
+    link(pair[0], pair[1], Center::new(4));
+    assert!(bicenter(pair[1]) == (pair[0], Some(pair[1]), 4));
 }
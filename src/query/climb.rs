@@ -0,0 +1,132 @@
+use crate::node::*;
+use crate::link::*;
+use crate::path_query::*;
+use crate::update::*;
+use crate::weight::*;
+
+// the total ascent and descent along a path, the vertex values being heights
+#[derive(Clone, Debug)]
+pub struct Climb<W = usize> {
+    pub up: W,
+    pub down: W,
+}
+
+// the difference of two heights, zero if `to` is lower
+fn rise<W: Weight + std::ops::Sub<Output = W>>(from: W, to: W) -> W {
+    if to > from { to - from } else { W::zero() }
+}
+
+impl<W: Weight + std::ops::Sub<Output = W>> Cluster for Climb<W> {
+    type V = W;
+    const UPDATE_VERTEX_REBUILDS_EDGES: bool = true;
+    fn identity() -> Self {
+        Climb { up: W::zero(), down: W::zero() }
+    }
+    fn compress(a: &Self, b: &Self, _: &W, _: &W, _: &W, out: &mut Self) {
+        *out = Climb {
+            up: a.up.weight_add(b.up),
+            down: a.down.weight_add(b.down),
+        };
+    }
+    fn rake(a: &Self, _: &Self, _: &W, _: &W, _: &W, out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.up, &mut self.down);
+    }
+    fn from_edge(_: &Self, a: &W, b: &W, out: &mut Self) {
+        *out = Climb {
+            up: rise(*a, *b),
+            down: rise(*b, *a),
+        };
+    }
+}
+
+pub fn climb_test() {
+    println!("climb");
+    let mut height = vec![5usize, 3, 8, 1, 4, 9, 2, 7, 6, 0, 5, 3, 8];
+    let v: Vec<_> = height.iter().map(|h| Vertex::new(*h)).collect();
+    let edges = [
+        (0usize, 1usize),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (0, 5),
+        (5, 9),
+        (9, 10),
+        (10, 11),
+        (10, 12),
+        (0, 6),
+        (6, 7),
+        (7, 8),
+    ];
+    for (a, b) in edges.iter() {
+        link(v[*a], v[*b], Climb::identity());
+    }
+    let mut par = [usize::MAX; 13];
+    let mut depth = [0; 13];
+    for (a, b) in edges.iter() {
+        par[*b] = *a;
+        depth[*b] = depth[*a] + 1;
+    }
+    let check = |height: &[usize]| {
+        for i in 0..13 {
+            for j in 0..13 {
+                if i == j { continue }
+                let (mut x, mut y) = (i, j);
+                let (mut up, mut down) = (0, 0);
+                let mut tail = Vec::new();
+                while x != y {
+                    if depth[x] >= depth[y] {
+                        let p = par[x];
+                        up += height[p].saturating_sub(height[x]);
+                        down += height[x].saturating_sub(height[p]);
+                        x = p;
+                    }
+                    else {
+                        tail.push(y);
+                        y = par[y];
+                    }
+                }
+                for &t in tail.iter().rev() {
+                    let p = par[t];
+                    up += height[t].saturating_sub(height[p]);
+                    down += height[p].saturating_sub(height[t]);
+                }
+                let res = path_query(v[i], v[j]);
+                assert!(res.up == up && res.down == down);
+                // the ends are folded in through `from_edge` on an empty edge, which climbs nothing
+                let res = path_query_inclusive(v[i], v[j]);
+                assert!(res.up == up && res.down == down);
+            }
+        }
+    };
+    check(&height);
+    for (i, h) in [(1usize, 10usize), (0, 0), (10, 7), (3, 2), (1, 1)].iter() {
+        height[*i] = *h;
+        update_vertex(v[*i], *h);
+        check(&height);
+    }
+    println!("climb 3 -> 12 = {:?}", path_query(v[3], v[12]));
+
+    // any `Weight` with a subtraction works as a height, negative ones included
+    let s: Vec<Vertex<Climb<i64>>> = [-5i64, 3, -2].iter().map(|h| Vertex::new(*h)).collect();
+    link(s[0], s[1], Climb::identity());
+    link(s[1], s[2], Climb::identity());
+    let res = path_query(s[0], s[2]);
+    assert!(res.up == 8 && res.down == 5);
+
+    // `from_edge` is called without `UPDATE_VERTEX_REBUILDS_EDGES` as well
+    #[derive(Clone, Debug)]
+    struct Gap(usize);
+    impl Cluster for Gap {
+        type V = usize;
+        fn identity() -> Self { Gap(0) }
+        fn compress(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { out.0 = a.0 + b.0 }
+        fn rake(a: &Self, _: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { out.0 = a.0 }
+        fn reverse(&mut self) {}
+        fn from_edge(_: &Self, a: &usize, b: &usize, out: &mut Self) { out.0 = a.abs_diff(*b) }
+    }
+    let g: Vec<Vertex<Gap>> = [5usize, 3, 8].iter().map(|h| Vertex::new(*h)).collect();
+    link(g[0], g[1], Gap(0));
+    link(g[2], g[1], Gap(0));
+    assert!(path_query(g[0], g[2]).0 == 7);
+}
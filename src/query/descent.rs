@@ -0,0 +1,109 @@
+//! The descent shared by `farthest` and `nearest_marked`: starting from one
+//! end of a cluster, walk down to the vertex the fold reports as best.
+
+use crate::node::*;
+use crate::expose::*;
+use crate::weight::*;
+
+// how a cluster keeps the best distance seen from each of its ends
+pub(crate) struct Best<T: Cluster, W> {
+    // the best distance from `endpoints(side)` to another vertex of the
+    // cluster, `None` if no vertex counts
+    pub dist: fn(&T, usize) -> Option<W>,
+    // the distance of a vertex from itself, `None` if it does not count
+    pub at: fn(Vertex<T>) -> Option<W>,
+    pub length: fn(&T) -> W,
+    // whether `a` is at least as good as `b`
+    pub better: fn(W, W) -> bool,
+}
+
+impl<T: Cluster, W: Weight> Best<T, W> {
+    // whether `a` is at least as good as `b`, `None` being the worst
+    fn ge(&self, a: Option<W>, b: Option<W>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => (self.better)(a, b),
+            (_, None) => true,
+            (None, Some(_)) => false,
+        }
+    }
+
+    // the better of the two, `a` on a tie
+    fn pick(&self, a: Option<W>, b: Option<W>) -> Option<W> {
+        if self.ge(a, b) { a } else { b }
+    }
+
+    // the leaf of the rake tree holding its best vertex, seen from the vertex it hangs from
+    pub fn rake(&self, mut rake: RakeNode<T>) -> CompNode<T> {
+        unsafe {
+            rake.push();
+            while let RakeNode::Node(r) = rake {
+                let (a, b) = (r.as_ref().child(0), r.as_ref().child(1));
+                rake = if self.ge((self.dist)(a.fold(), 1), (self.dist)(b.fold(), 1)) { a } else { b };
+                rake.push();
+            }
+            if let RakeNode::Leaf(comp) = rake { comp }
+            else { unreachable!() }
+        }
+    }
+
+    // the best vertex seen from `node.endpoints(side)` inside `node`
+    pub fn comp(&self, mut node: CompNode<T>, mut side: usize) -> Vertex<T> {
+        unsafe {
+            node.push();
+            while let CompNode::Node(n) = node {
+                let near = n.as_ref().child(side);
+                let far = n.as_ref().child(side ^ 1);
+                let mid = near.endpoints(side ^ 1);
+                let far_dist = (self.dist)(far.fold(), side);
+                let rake_dist = n.as_ref().rake().and_then(|r| (self.dist)(r.fold(), 1));
+                let mid_dist = (self.at)(mid);
+                let beyond = self.pick(far_dist, rake_dist);
+                let inside = (self.dist)(near.fold(), side);
+                let length = (self.length)(near.fold());
+                if inside.is_some() && self.ge(inside, self.pick(mid_dist, beyond).map(|d| length.weight_add(d))) {
+                    node = near;
+                }
+                else if mid_dist.is_some() && self.ge(mid_dist, beyond) {
+                    return mid;
+                }
+                else if far_dist.is_some() && self.ge(far_dist, rake_dist) {
+                    node = far;
+                }
+                else {
+                    node = self.rake(n.as_ref().rake().unwrap());
+                    side = 1;
+                }
+                node.push();
+            }
+            let far = node.endpoints(side ^ 1);
+            if far.is_dummy() { node.endpoints(side) } else { far }
+        }
+    }
+
+    // the best vertex seen from `v` in its tree and its distance, `v` itself on a tie
+    pub fn from(&self, v: Vertex<T>) -> Option<(Vertex<T>, W)> {
+        let at = (self.at)(v);
+        unsafe {
+            let mut root = expose(v);
+            root.push();
+            match root {
+                CompNode::Node(n) if n.as_ref().child(0).endpoints(1) == v => {
+                    let (a, b) = (n.as_ref().child(0), n.as_ref().child(1));
+                    let (da, db) = ((self.dist)(a.fold(), 1), (self.dist)(b.fold(), 0));
+                    let dr = n.as_ref().rake().and_then(|r| (self.dist)(r.fold(), 1));
+                    let best = self.pick(at, self.pick(da, self.pick(db, dr)))?;
+                    let x = if at == Some(best) { v }
+                            else if da == Some(best) { self.comp(a, 1) }
+                            else if db == Some(best) { self.comp(b, 0) }
+                            else { self.comp(self.rake(n.as_ref().rake().unwrap()), 1) };
+                    Some((x, best))
+                }
+                _ => {
+                    let side = if root.endpoints(0) == v { 0 } else { 1 };
+                    let best = self.pick(at, (self.dist)(root.fold(), side))?;
+                    Some((if at == Some(best) { v } else { self.comp(root, side) }, best))
+                }
+            }
+        }
+    }
+}
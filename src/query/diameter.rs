@@ -3,17 +3,20 @@ use crate::link::*;
 use crate::cut::*;
 //use crate::select::*;
 use crate::expose::*;
+use crate::path_query::*;
+use crate::weight::*;
+use crate::query::descent::*;
 
 #[derive(Clone, Debug)]
-struct Diameter {
-    diam: usize,
-    max_dist_left: usize,
-    max_dist_right: usize,
-    length: usize
+pub struct Diameter<W = usize> {
+    pub diam: W,
+    pub max_dist_left: W,
+    pub max_dist_right: W,
+    pub length: W
 }
 
-impl Diameter {
-    fn new(l: usize) -> Self {
+impl<W: Weight> Diameter<W> {
+    pub fn new(l: W) -> Self {
         Diameter {
             diam: l,
             max_dist_left: l,
@@ -23,37 +26,129 @@ impl Diameter {
     }
 }
 
-impl Cluster for Diameter {
+impl<W: Weight> Cluster for Diameter<W> {
     type V = usize;
     fn identity() -> Self {
         Diameter {
-            diam: 0,
-            max_dist_left: 0,
-            max_dist_right: 0,
-            length: 0,
+            diam: W::zero(),
+            max_dist_left: W::zero(),
+            max_dist_right: W::zero(),
+            length: W::zero(),
         }
     }
-    fn compress(a: Self, b: Self, _: usize, _: usize, _: usize) -> Self {
-        Diameter {
-            diam: *[ a.diam, b.diam, a.max_dist_right + b.max_dist_left].into_iter().max().unwrap(),
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_left),
-            max_dist_right: std::cmp::max(b.max_dist_right, b.length + a.max_dist_right),
-            length: a.length + b.length
-        }
+    fn compress(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        *out = Diameter {
+            diam: *[ a.diam, b.diam, a.max_dist_right.weight_add(b.max_dist_left)].iter().max().unwrap(),
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_left)),
+            max_dist_right: std::cmp::max(b.max_dist_right, b.length.weight_add(a.max_dist_right)),
+            length: a.length.weight_add(b.length)
+        };
     }
-    fn rake(a: Self, b: Self, _: usize, _: usize, _: usize) -> Self {
-        Diameter {
-            diam: *[ a.diam, b.diam, a.max_dist_right + b.max_dist_right ].into_iter().max().unwrap(),
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_right),
+    fn rake(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        *out = Diameter {
+            diam: *[ a.diam, b.diam, a.max_dist_right.weight_add(b.max_dist_right)].iter().max().unwrap(),
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_right)),
             max_dist_right: std::cmp::max(a.max_dist_right, b.max_dist_right),
             length: a.length,
-        }
+        };
     }
     fn reverse(&mut self) {
         std::mem::swap(&mut self.max_dist_left, &mut self.max_dist_right);
     }
 }
 
+// the farthest vertex is the best one, and every vertex counts at distance zero from itself
+fn farthest_best<W: Weight>() -> Best<Diameter<W>, W> {
+    Best {
+        dist: |c, side| Some(if side == 0 { c.max_dist_left } else { c.max_dist_right }),
+        at: |_| Some(W::zero()),
+        length: |c| c.length,
+        better: |a, b| a >= b,
+    }
+}
+
+fn farthest_rake<W: Weight>(rake: RakeNode<Diameter<W>>) -> CompNode<Diameter<W>> {
+    farthest_best().rake(rake)
+}
+
+// the farthest vertex from `node.endpoints(side)` inside `node`
+fn farthest_comp<W: Weight>(node: CompNode<Diameter<W>>, side: usize) -> Vertex<Diameter<W>> {
+    farthest_best().comp(node, side)
+}
+
+/// A vertex farthest from `v` in its tree and the distance to it. `v` itself
+/// counts at distance zero, so an isolated vertex gives `(v, 0)`, and so do
+/// trees whose edges are all negative. Any weights are fine.
+pub fn farthest<W: Weight>(v: Vertex<Diameter<W>>) -> (Vertex<Diameter<W>>, W) {
+    farthest_best().from(v).unwrap()
+}
+
+// the ends of `node` that stand for real vertices
+fn leaf_ends<W: Weight>(node: CompNode<Diameter<W>>) -> (Vertex<Diameter<W>>, Vertex<Diameter<W>>) {
+    let (x, y) = (node.endpoints(0), node.endpoints(1));
+    if x.is_dummy() { (y, y) }
+    else if y.is_dummy() { (x, x) }
+    else { (x, y) }
+}
+
+// both ends of a path of length `node.fold().diam` inside `node`, found by
+// following whichever term of the fold gave the maximum
+fn diameter_comp<W: Weight>(mut node: CompNode<Diameter<W>>) -> (Vertex<Diameter<W>>, Vertex<Diameter<W>>) {
+    unsafe {
+        node.push();
+        while let CompNode::Node(n) = node {
+            let d = node.fold().diam;
+            let (a, b) = (n.as_ref().child(0), n.as_ref().child(1));
+            let (af, bf) = (a.fold(), b.fold());
+            if af.diam == d {
+                node = a;
+            }
+            else if bf.diam == d {
+                node = b;
+            }
+            else if let Some(r) = n.as_ref().rake() {
+                let rf = r.fold();
+                if rf.diam == d {
+                    // descend the rake tree the same way
+                    let mut rake = r;
+                    while let RakeNode::Node(x) = rake {
+                        let d = rake.fold().diam;
+                        let (p, q) = (x.as_ref().child(0), x.as_ref().child(1));
+                        if p.fold().diam == d { rake = p }
+                        else if q.fold().diam == d { rake = q }
+                        else { return (farthest_comp(farthest_rake(p), 1), farthest_comp(farthest_rake(q), 1)) }
+                    }
+                    node = if let RakeNode::Leaf(comp) = rake { comp } else { unreachable!() };
+                }
+                else if af.max_dist_right.weight_add(rf.max_dist_right) == d {
+                    return (farthest_comp(a, 1), farthest_comp(farthest_rake(r), 1));
+                }
+                else {
+                    // through the middle vertex, from the farther of `a` and the rake
+                    let x = if af.max_dist_right >= rf.max_dist_right { farthest_comp(a, 1) }
+                            else { farthest_comp(farthest_rake(r), 1) };
+                    return (x, farthest_comp(b, 0));
+                }
+            }
+            else {
+                return (farthest_comp(a, 1), farthest_comp(b, 0));
+            }
+            node.push();
+        }
+        leaf_ends(node)
+    }
+}
+
+/// Both ends of a longest path in the tree containing `v`, and its length.
+/// Any weights are fine: the descent follows the `diam` of the folds rather
+/// than sweeping with `farthest`. With no path longer than zero, both ends are
+/// the same vertex.
+pub fn diameter<W: Weight>(v: Vertex<Diameter<W>>) -> (Vertex<Diameter<W>>, Vertex<Diameter<W>>, W) {
+    let root = expose(v);
+    let (x, y) = diameter_comp(root);
+    (x, y, root.fold().diam)
+}
+
 use std::io::Read;
 
 pub fn diameter_test() {
@@ -63,7 +158,7 @@ pub fn diameter_test() {
     let mut iter = buf.split_whitespace();
     let n: usize = iter.next().unwrap().parse().unwrap();
 
-    let v: Vec<_> = (0..n).map(|i| Vertex::new(i)).collect();
+    let v: Vec<_> = (0..n).map(Vertex::new).collect();
     let edges :Vec<(usize, usize, usize)>= (0..n-1).map(|_| {
         (
             iter.next().unwrap().parse().unwrap(),
@@ -82,7 +177,7 @@ pub fn diameter_test() {
 
 pub fn diameter_cut_test() {
     println!("diameter cut");
-    let v: Vec<_> = (0..13).map(|i| Vertex::new(i)).collect();
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
     let edges = [
         (0usize, 1usize, 1usize),
         (1, 2, 10),
@@ -108,7 +203,45 @@ pub fn diameter_cut_test() {
         else { 1 }
     }); 
     println!("center vertices {}, {}", center.0.value(), center.1.value()); */
+    let (w, left, right) = cut(v[0], v[5]);
+    println!("0 diameter = {}", left.diam);
+    println!("5 diameter = {}", right.diam);
+    assert!(w.length == 3);
+    assert!(left.diam == 24 && left.diam == expose(v[0]).fold().diam);
+    assert!(right.diam == 20 && right.diam == expose(v[5]).fold().diam);
+}
+
+pub fn diameter_endpoints_test() {
+    println!("diameter endpoints");
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
+    let edges = [
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Diameter::new(*w));
+    }
+    for x in v.iter() {
+        let (a, b, d) = diameter(*x);
+        assert!(d == 36 && path_query(a, b).length == 36);
+        let (f, fd) = farthest(*x);
+        assert!(*x == f || path_query(*x, f).length == fd);
+    }
+    let (a, b, _) = diameter(v[0]);
+    println!("diameter {} - {}", a.value(), b.value());
     cut(v[0], v[5]);
-    println!("0 diameter = {}", expose(v[0]).fold().diam);
-    println!("5 diameter = {}", expose(v[5]).fold().diam);
+    let (a, b, d) = diameter(v[5]);
+    assert!(d == 20 && path_query(a, b).length == 20);
+    let single = Vertex::new(13);
+    assert!(diameter(single) == (single, single, 0));
 }
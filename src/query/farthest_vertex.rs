@@ -1,18 +1,20 @@
 use crate::node::*;
 use crate::link::*;
 use crate::cut::*;
+use crate::fold::*;
 use crate::expose::*;
+use crate::weight::*;
 
 #[derive(Clone, Debug)]
-struct Farthest {
-    ans: usize,
-    max_dist_left: usize,
-    max_dist_right: usize,
-    length: usize
+struct Farthest<W = usize> {
+    ans: W,
+    max_dist_left: W,
+    max_dist_right: W,
+    length: W
 }
 
-impl Farthest {
-    fn new(l: usize) -> Self {
+impl<W: Weight> Farthest<W> {
+    fn new(l: W) -> Self {
         Farthest {
             ans: l,
             max_dist_left: l,
@@ -22,30 +24,31 @@ impl Farthest {
     }
 }
 
-impl Cluster for Farthest {
+impl<W: Weight> Cluster for Farthest<W> {
+    type V = ();
     fn identity() -> Self {
         Farthest {
-            ans: 0,
-            max_dist_left: 0,
-            max_dist_right: 0,
-            length: 0,
+            ans: W::zero(),
+            max_dist_left: W::zero(),
+            max_dist_right: W::zero(),
+            length: W::zero(),
         }
     }
-    fn compress(a: Self, b: Self) -> Self {
-        Farthest {
+    fn compress(a: &Self, b: &Self, _: &(), _: &(), _: &(), out: &mut Self) {
+        *out = Farthest {
             ans: std::cmp::max(a.max_dist_right, b.max_dist_left),
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_left),
-            max_dist_right: std::cmp::max(b.max_dist_right, b.length + a.max_dist_right),
-            length: a.length + b.length
-        }
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_left)),
+            max_dist_right: std::cmp::max(b.max_dist_right, b.length.weight_add(a.max_dist_right)),
+            length: a.length.weight_add(b.length)
+        };
     }
-    fn rake(a: Self, b: Self) -> Self {
-        Farthest {
-            ans: 0,
-            max_dist_left: std::cmp::max(a.max_dist_left, a.length + b.max_dist_right),
+    fn rake(a: &Self, b: &Self, _: &(), _: &(), _: &(), out: &mut Self) {
+        *out = Farthest {
+            ans: W::zero(),
+            max_dist_left: std::cmp::max(a.max_dist_left, a.length.weight_add(b.max_dist_right)),
             max_dist_right: std::cmp::max(a.max_dist_right, b.max_dist_right),
             length: a.length,
-        }
+        };
     }
     fn reverse(&mut self) {
         std::mem::swap(&mut self.max_dist_left, &mut self.max_dist_right);
@@ -86,7 +89,49 @@ pub fn farthest_test() {
             link(v[p], v[q], Farthest::new(*c));
         }
         else if *t == 3 {
-            println!("farthest from {} = {}", *a, expose(v[*a]).fold().ans);
+            println!("farthest from {} = {}", *a, component_fold(v[*a]).max_dist_left);
         }
     }
 }
+
+pub fn farthest_static_test() {
+    println!("farthest static");
+    let v: Vec<_> = (0..13).map(|_| Vertex::new(())).collect();
+    let edges = [
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    let mut adj = vec![Vec::new(); 13];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Farthest::new(*w));
+        adj[*a].push((*b, *w));
+        adj[*b].push((*a, *w));
+    }
+    fn far(adj: &[Vec<(usize, usize)>], v: usize, p: usize) -> usize {
+        adj[v].iter().filter(|(u, _)| *u != p).map(|(u, w)| w + far(adj, *u, v)).max().unwrap_or(0)
+    }
+    for (i, vv) in v.iter().enumerate() {
+        assert!(component_fold(*vv).max_dist_left == far(&adj, i, usize::MAX));
+        assert!(expose(*vv).fold().ans == far(&adj, i, usize::MAX));
+    }
+    cut(v[0], v[5]);
+    link(v[5], v[0], Farthest::new(20));
+    for (x, y) in [(0usize, 5usize), (5, 0)].iter() {
+        adj[*x].iter_mut().find(|(u, _)| u == y).unwrap().1 = 20;
+    }
+    for (i, vv) in v.iter().enumerate() {
+        assert!(component_fold(*vv).max_dist_left == far(&adj, i, usize::MAX));
+        assert!(expose(*vv).fold().ans == far(&adj, i, usize::MAX));
+    }
+    println!("farthest from 8 = {}", component_fold(v[8]).max_dist_left);
+}
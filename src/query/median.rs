@@ -1,68 +1,103 @@
 use crate::node::*;
 use crate::link::*;
 use crate::select::*;
-use crate::expose::*;
+use crate::fold::*;
 use crate::path_query::*;
 use crate::cut::*;
+use crate::update::*;
+use crate::expose::*;
+use crate::weight::*;
 
 #[derive(Clone, Debug)]
-struct Median {
-    inter_weight: usize,
-    left_sum: usize,
-    right_sum: usize,
-    ans: usize,
-    length: usize,
+pub struct Median<W = usize, D = W> {
+    pub inter_weight: W,
+    pub left_sum: D,
+    pub right_sum: D,
+    pub ans: D,
+    pub length: D,
 }
 
-impl Median {
-    fn new(l: usize) -> Self {
+impl<W: Weight, D: Weight> Median<W, D> {
+    pub fn new(l: D) -> Self {
         Median {
-            inter_weight: 0,
-            ans: 0,
-            left_sum: 0,
-            right_sum: 0,
+            inter_weight: W::zero(),
+            left_sum: D::zero(),
+            right_sum: D::zero(),
+            ans: D::zero(),
             length: l,
         }
     }
 }
 
-impl Cluster for Median {
-    type V = usize;
+// lengths times weights saturate instead of overflowing
+impl<W: Weight, D: Weight + From<W>> Cluster for Median<W, D> {
+    type V = W;
     fn identity() -> Self {
         Median {
-            inter_weight: 0,
-            left_sum: 0,
-            right_sum: 0,
-            ans: 0,
-            length: 0,
+            inter_weight: W::zero(),
+            left_sum: D::zero(),
+            right_sum: D::zero(),
+            ans: D::zero(),
+            length: D::zero(),
         }
     }
-    fn compress(a: Self, b: Self, av: usize, bv: usize, cv: usize) -> Self {
-        Median {
-            inter_weight: a.inter_weight + b.inter_weight + cv,
-            ans: a.right_sum + b.left_sum + a.length * av + b.length * bv,
-            left_sum: a.left_sum + b.left_sum + a.length * (b.inter_weight + cv),
-            right_sum: b.right_sum + a.right_sum + b.length * (a.inter_weight + cv),
-            length: a.length + b.length,
-        }
+    fn compress(a: &Self, b: &Self, av: &W, bv: &W, cv: &W, out: &mut Self) {
+        *out = Median {
+            inter_weight: a.inter_weight.weight_add(b.inter_weight).weight_add(*cv),
+            ans: a.right_sum.weight_add(b.left_sum).weight_add(a.length.saturating_mul(D::from(*av))).weight_add(b.length.saturating_mul(D::from(*bv))),
+            left_sum: a.left_sum.weight_add(b.left_sum).weight_add(a.length.saturating_mul(D::from(b.inter_weight.weight_add(*cv)))),
+            right_sum: b.right_sum.weight_add(a.right_sum).weight_add(b.length.saturating_mul(D::from(a.inter_weight.weight_add(*cv)))),
+            length: a.length.weight_add(b.length),
+        };
     }
-    fn rake(a: Self, b: Self, _av: usize, bv: usize, _cv: usize) -> Self {
-        Median {
-            inter_weight: a.inter_weight + b.inter_weight + bv,
-            ans: 0,
-            left_sum: a.left_sum + b.right_sum + a.length * b.inter_weight + (a.length + b.length) * bv,
-            right_sum: a.right_sum + b.right_sum + b.length * bv,
+    fn rake(a: &Self, b: &Self, _av: &W, bv: &W, _cv: &W, out: &mut Self) {
+        *out = Median {
+            inter_weight: a.inter_weight.weight_add(b.inter_weight).weight_add(*bv),
+            left_sum: a.left_sum.weight_add(b.right_sum).weight_add(a.length.saturating_mul(D::from(b.inter_weight))).weight_add(a.length.weight_add(b.length).saturating_mul(D::from(*bv))),
+            right_sum: a.right_sum.weight_add(b.right_sum).weight_add(b.length.saturating_mul(D::from(*bv))),
+            ans: D::zero(),
             length: a.length,
-        }
+        };
     }
     fn reverse(&mut self) {
         std::mem::swap(&mut self.left_sum, &mut self.right_sum);
     }
 }
 
+/// The `sel` of `weighted_median`: go to the heavier half. With
+/// `select_in_subtree` it finds the weighted median of a subtree among the two
+/// endpoints returned.
+pub fn toward_median<W: Weight, D: Weight + From<W>>(a: &Median<W, D>, b: &Median<W, D>, av: &W, bv: &W, cv: &W) -> usize {
+    if a.inter_weight.weight_add(*av).weight_add(*cv) >= b.inter_weight.weight_add(*bv).weight_add(*cv) { 0 }
+    else { 1 }
+}
+
+/// A vertex minimising the sum of its distances to the vertices of its tree,
+/// each multiplied by the vertex weight, and that sum. The edge weights must
+/// be non-negative. Products and sums saturate at the bounds of `D`, so pick
+/// a `D` wide enough for the largest cost.
+pub fn weighted_median<W: Weight, D: Weight + From<W>>(v: Vertex<Median<W, D>>) -> (Vertex<Median<W, D>>, D) {
+    let (x, y) = select(v, toward_median);
+    if x.is_dummy() {
+        (y, component_fold(y).left_sum)
+    }
+    else if y.is_dummy() {
+        (x, component_fold(x).left_sum)
+    }
+    else {
+        let (cx, cy) = (component_fold(x).left_sum, component_fold(y).left_sum);
+        if cy < cx { (y, cy) } else { (x, cx) }
+    }
+}
+
+/// Replaces the weight of `v`, like `update_vertex`.
+pub fn set_vertex_weight<W: Weight, D: Weight + From<W>>(v: Vertex<Median<W, D>>, w: W) {
+    update_vertex(v, w);
+}
+
 pub fn median_test() {
     println!("median test");
-    let v: Vec<_> = (0..13).map(|_| Vertex::new(1)).collect();
+    let v: Vec<Vertex<Median>> = (0..13).map(|_| Vertex::new(1)).collect();
     let edges = [
         (0usize, 1usize, 1usize),
         (1, 2, 10),
@@ -82,12 +117,12 @@ pub fn median_test() {
         es.push(link(v[*a], v[*b], Median::new(*w)));
     }
     let median = select(v[0], |a, b, av, bv, cv| {
-        if a.inter_weight + av + cv >= b.inter_weight + bv + cv { 0 }
+        if a.inter_weight + *av + *cv >= b.inter_weight + *bv + *cv { 0 }
         else { 1 }
     });
     println!("median vertices {}, {}", v.iter().position(|vv| *vv == median.0).unwrap(), v.iter().position(|vv| *vv == median.1).unwrap());
-    println!("weight {}, {}", expose(median.0).fold().ans, expose(median.1).fold().ans);
-    println!("9 weight => {}", expose(v[9]).fold().ans);
+    println!("weight {}, {}", component_fold(median.0).left_sum, component_fold(median.1).left_sum);
+    println!("9 weight => {}", component_fold(v[9]).left_sum);
     println!("9 = median.1 => {}", v[9] == median.1);
 
     println!("verify");
@@ -99,8 +134,10 @@ pub fn median_test() {
             sum += path_query(v[i], v[j]).length;
         }
         println!("center {} -> sum = {}", i, sum);
-        println!("expose sum = {}", expose(v[i]).fold().ans);
-        println!("inter = {}", expose(v[i]).fold().inter_weight);
+        assert!(component_fold(v[i]).left_sum == sum);
+        // `ans` of the root is the same sum, seen from its middle vertex
+        assert!(expose(v[i]).fold().ans == sum);
+        println!("inter = {}", component_fold(v[i]).inter_weight);
     }
 }
 
@@ -112,7 +149,7 @@ pub fn yuki772() {
     let mut iter = buf.split_whitespace();
     let n: usize = iter.next().unwrap().parse().unwrap();
     let q: usize = iter.next().unwrap().parse().unwrap();
-    let mut v: Vec<_> = (0..n).map(|_| Vertex::new(1)).collect();
+    let v: Vec<Vertex<Median>> = (0..n).map(|_| Vertex::new(1)).collect();
     let mut sum = 0;
     for _ in 0..q {
         let query: usize = iter.next().unwrap().parse().unwrap();
@@ -132,18 +169,110 @@ pub fn yuki772() {
         else if query == 3 {
             let a: usize = iter.next().unwrap().parse().unwrap();
             let a = (a - 1 + sum) % n;
-            let mut root = expose(v[a]);
             let val = v[a].value();
-            v[a].value_set(1 - val);
-            root.fix();
-            let (x, y) = select(v[a], |a, b, av, bv, cv| {
-                if a.inter_weight + av + cv >= b.inter_weight + bv + cv { 0 }
-                else { 1 }
-            });
-            let ans = std::cmp::min(expose(x).fold().ans, expose(y).fold().ans);
+            set_vertex_weight(v[a], 1 - val);
+            let (_, ans) = weighted_median(v[a]);
             sum = (sum + ans % n) % n;
             println!("{}", ans);
         }
     }
 
 }
+
+pub fn weighted_median_test() {
+    println!("weighted median");
+    let mut weight = vec![3u32, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 8, 9];
+    let v: Vec<Vertex<Median<u32, TotalF64>>> = weight.iter().map(|w| Vertex::new(*w)).collect();
+    let edges = [
+        (0usize, 1usize, 1.5f64),
+        (1, 2, 10.0),
+        (1, 3, 3.25),
+        (1, 4, 4.0),
+        (0, 5, 3.0),
+        (5, 9, 4.5),
+        (9, 10, 7.0),
+        (10, 11, 9.0),
+        (10, 12, 1.0),
+        (0, 6, 3.0),
+        (6, 7, 0.5),
+        (7, 8, 7.0),
+    ];
+    let mut adj = vec![Vec::new(); 13];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Median::new(TotalF64(*w)));
+        adj[*a].push((*b, *w));
+        adj[*b].push((*a, *w));
+    }
+    // distances from a dfs over `edges`, not from the top tree under test
+    let dist: Vec<Vec<f64>> = (0..13).map(|i| {
+        let mut dist = vec![None; 13];
+        let mut stack = vec![(i, 0.0)];
+        while let Some((y, dy)) = stack.pop() {
+            dist[y] = Some(dy);
+            stack.extend(adj[y].iter().filter(|(z, _)| dist[*z].is_none()).map(|(z, w)| (*z, dy + w)));
+        }
+        dist.into_iter().map(Option::unwrap).collect()
+    }).collect();
+    let check = |weight: &[u32]| {
+        let cost: Vec<f64> = (0..13).map(|i| {
+            (0..13).map(|j| dist[i][j] * f64::from(weight[j])).sum()
+        }).collect();
+        let best = cost.iter().cloned().fold(f64::INFINITY, f64::min);
+        for x in v.iter() {
+            let (m, c) = weighted_median(*x);
+            assert!((c.0 - best).abs() < 1e-9);
+            assert!((cost[v.iter().position(|y| *y == m).unwrap()] - best).abs() < 1e-9);
+        }
+        best
+    };
+    check(&weight);
+    for (i, w) in [(12usize, 0u32), (2, 40), (8, 30), (2, 0)].iter() {
+        weight[*i] = *w;
+        set_vertex_weight(v[*i], *w);
+        println!("min cost = {}", check(&weight));
+    }
+}
+
+pub fn subtree_median_test() {
+    println!("subtree median");
+    let weight = [3usize, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 8, 9];
+    let v: Vec<Vertex<Median>> = weight.iter().map(|w| Vertex::new(*w)).collect();
+    let edges = [
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    let mut adj = vec![Vec::new(); 13];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Median::new(*w));
+        adj[*a].push(*b);
+        adj[*b].push(*a);
+    }
+    fn side(adj: &[Vec<usize>], x: usize, p: usize, out: &mut Vec<usize>) {
+        out.push(x);
+        for &y in adj[x].iter().filter(|y| **y != p) {
+            side(adj, y, x, out);
+        }
+    }
+    for (a, b, _) in edges.iter() {
+        for &(x, p) in [(*a, *b), (*b, *a)].iter() {
+            let mut sub = Vec::new();
+            side(&adj, x, p, &mut sub);
+            let cost = |i: usize| -> usize { sub.iter().filter(|j| **j != i).map(|j| path_query(v[i], v[*j]).length * weight[*j]).sum() };
+            let best = sub.iter().map(|i| cost(*i)).min().unwrap();
+            let (m0, m1) = select_in_subtree(v[x], v[p], toward_median);
+            let found: Vec<usize> = [m0, m1].iter().filter(|m| !m.is_dummy()).map(|m| v.iter().position(|y| y == m).unwrap()).collect();
+            assert!(found.iter().all(|i| sub.contains(i)));
+            assert!(found.iter().map(|i| cost(*i)).min() == Some(best));
+        }
+    }
+}
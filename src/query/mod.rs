@@ -1,5 +1,14 @@
 pub mod diameter;
 pub mod path_length;
-//pub mod farthest_vertex;
+pub mod farthest_vertex;
 pub mod center;
 pub mod median;
+pub mod path_max_edge;
+pub mod climb;
+pub mod vertex_path;
+pub mod subtree_sum;
+pub mod nearest_marked;
+pub mod path_monoid;
+pub(crate) mod descent;
+#[cfg(feature = "derive")]
+pub mod product;
@@ -0,0 +1,149 @@
+use crate::node::*;
+use crate::link::*;
+use crate::cut::*;
+use crate::update::*;
+use crate::weight::*;
+use crate::query::descent::*;
+
+#[derive(Clone, Debug)]
+pub struct NearestMarked<W = usize> {
+    pub near_left: Option<W>,
+    pub near_right: Option<W>,
+    pub length: W,
+}
+
+impl<W: Weight> NearestMarked<W> {
+    pub fn new(l: W) -> Self {
+        NearestMarked {
+            near_left: None,
+            near_right: None,
+            length: l,
+        }
+    }
+}
+
+fn min_opt<W: Weight>(a: Option<W>, b: Option<W>) -> Option<W> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn mark_dist<W: Weight>(marked: bool, d: W) -> Option<W> {
+    if marked { Some(d) } else { None }
+}
+
+impl<W: Weight> Cluster for NearestMarked<W> {
+    type V = bool;
+    fn identity() -> Self {
+        NearestMarked::new(W::zero())
+    }
+    fn compress(a: &Self, b: &Self, _: &bool, _: &bool, c: &bool, out: &mut Self) {
+        *out = NearestMarked {
+            near_left: min_opt(a.near_left, min_opt(mark_dist(*c, a.length), b.near_left.map(|d| a.length.weight_add(d)))),
+            near_right: min_opt(b.near_right, min_opt(mark_dist(*c, b.length), a.near_right.map(|d| b.length.weight_add(d)))),
+            length: a.length.weight_add(b.length),
+        };
+    }
+    fn rake(a: &Self, b: &Self, _: &bool, bv: &bool, _: &bool, out: &mut Self) {
+        *out = NearestMarked {
+            near_left: min_opt(a.near_left, min_opt(mark_dist(*bv, a.length.weight_add(b.length)), b.near_right.map(|d| a.length.weight_add(d)))),
+            near_right: min_opt(a.near_right, min_opt(mark_dist(*bv, b.length), b.near_right)),
+            length: a.length,
+        };
+    }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.near_left, &mut self.near_right);
+    }
+}
+
+// the nearest marked vertex is the best one, and only marked vertices count
+fn nearest_best<W: Weight>() -> Best<NearestMarked<W>, W> {
+    Best {
+        dist: |c, side| if side == 0 { c.near_left } else { c.near_right },
+        at: |v| mark_dist(*v.value_ref(), W::zero()),
+        length: |c| c.length,
+        better: |a, b| a <= b,
+    }
+}
+
+/// The marked vertex nearest to `v` and the distance to it, `v` itself if it
+/// is marked, or `None` if its tree has no marked vertex. The edge weights
+/// must be non-negative.
+pub fn nearest_marked<W: Weight>(v: Vertex<NearestMarked<W>>) -> Option<(Vertex<NearestMarked<W>>, W)> {
+    nearest_best().from(v)
+}
+
+/// Marks `v`, like `update_vertex(v, true)`.
+pub fn mark<W: Weight>(v: Vertex<NearestMarked<W>>) {
+    update_vertex(v, true);
+}
+
+/// Unmarks `v`, like `update_vertex(v, false)`.
+pub fn unmark<W: Weight>(v: Vertex<NearestMarked<W>>) {
+    update_vertex(v, false);
+}
+
+pub fn nearest_marked_test() {
+    println!("nearest marked");
+    let v: Vec<_> = (0..13).map(|_| Vertex::new(false)).collect();
+    let mut edges = vec![
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], NearestMarked::new(*w));
+    }
+    let mut marked = [false; 13];
+    let check = |edges: &[(usize, usize, usize)], marked: &[bool]| {
+        let mut adj = vec![Vec::new(); 13];
+        for (a, b, w) in edges.iter() {
+            adj[*a].push((*b, *w));
+            adj[*b].push((*a, *w));
+        }
+        fn near(adj: &[Vec<(usize, usize)>], marked: &[bool], v: usize, p: usize) -> Option<usize> {
+            if marked[v] { return Some(0) }
+            adj[v].iter().filter(|(u, _)| *u != p).filter_map(|(u, w)| near(adj, marked, *u, v).map(|d| d + w)).min()
+        }
+        for i in 0..13 {
+            let expect = near(&adj, marked, i, usize::MAX);
+            match nearest_marked(v[i]) {
+                Some((x, d)) => {
+                    let xi = v.iter().position(|y| *y == x).unwrap();
+                    assert!(Some(d) == expect && marked[xi]);
+                    let mut dist = vec![None; 13];
+                    let mut stack = vec![(i, 0)];
+                    while let Some((y, dy)) = stack.pop() {
+                        dist[y] = Some(dy);
+                        stack.extend(adj[y].iter().filter(|(z, _)| dist[*z].is_none()).map(|(z, w)| (*z, dy + w)));
+                    }
+                    assert!(dist[xi] == Some(d));
+                }
+                None => assert!(expect.is_none()),
+            }
+        }
+    };
+    check(&edges, &marked);
+    for &(i, m) in [(8usize, true), (2, true), (12, true), (8, false), (0, true), (2, false), (12, false), (0, false)].iter() {
+        marked[i] = m;
+        if m { mark(v[i]); } else { unmark(v[i]); }
+        check(&edges, &marked);
+    }
+    mark(v[11]);
+    marked[11] = true;
+    cut(v[0], v[5]);
+    edges.remove(4);
+    check(&edges, &marked);
+    println!("nearest from 9 = {:?}", nearest_marked(v[9]).map(|(_, d)| d));
+}
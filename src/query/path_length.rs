@@ -1,19 +1,28 @@
 use crate::node::*;
+use crate::weight::*;
 use crate::link::*;
 use crate::path_query::*;
 use crate::debug::*;
 
-impl Cluster for usize {
-    type V = usize;
-    fn identity() -> Self { 0 }
-    fn compress(left: Self, right: Self, _: usize, _: usize, _: usize) -> Self { left + right }
-    fn rake(a: Self, _: Self, _: usize, _: usize, _: usize) -> Self { a }
-    fn reverse(&mut self) {}
+// a weight is the length of its path; one impl per type rather than one for
+// every `Weight`, which would keep any other `Weight` type from being a cluster
+macro_rules! length_cluster {
+    ($($t:ty),*) => {$(
+        impl Cluster for $t {
+            type V = usize;
+            fn identity() -> Self { <$t as Weight>::zero() }
+            fn compress(left: &Self, right: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { *out = Weight::weight_add(*left, *right) }
+            fn rake(a: &Self, _: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { *out = *a }
+            fn reverse(&mut self) {}
+        }
+    )*};
 }
 
+length_cluster!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, TotalF64);
+
 pub fn path_length_test() {
     println!("path_length");
-    let v: Vec<_> = (0..13).map(|i| Vertex::new(i)).collect();
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
     let edges = [
         (0usize, 1usize, 1usize),
         (1, 2, 10),
@@ -40,11 +49,17 @@ pub fn path_length_test() {
     assert!(path_query(v[3], v[11]) == 27);
     assert!(path_query(v[6], v[12]) == 18);
     assert!(path_query(v[12], v[6]) == 18);
-    for i in 0..13 {
+    for (i, vv) in v.iter().enumerate() {
         println!("VERTEX {} ---------------", i);
-        test_comp_set(v[i].handle().unwrap());
+        test_comp_set(vv.handle().unwrap());
     }
     assert!(path_query(v[2], v[4]) == 14);
     assert!(path_query(v[5], v[6]) == 6);
+
+    let tree = fmt_cluster_tree(v[7]);
+    println!("{}", tree);
+    // 12 edges and one dummy edge per vertex
+    assert!(tree.matches("Edge [").count() == 25);
+    assert!(tree == fmt_cluster_tree(v[3]));
 }
 
@@ -0,0 +1,115 @@
+use std::ptr::NonNull;
+use crate::node::*;
+use crate::link::*;
+use crate::cut::*;
+use crate::path_query::*;
+use crate::raw;
+use crate::weight::*;
+
+#[derive(Clone, Debug)]
+pub struct PathMaxEdge<W: Weight = usize> {
+    pub max: W,
+    pub edge: Option<NonNull<Edge<PathMaxEdge<W>>>>,
+}
+
+impl<W: Weight> PathMaxEdge<W> {
+    pub fn new(w: W, e: NonNull<Edge<PathMaxEdge<W>>>) -> Self {
+        PathMaxEdge {
+            max: w,
+            edge: Some(e),
+        }
+    }
+}
+
+impl<W: Weight> Cluster for PathMaxEdge<W> {
+    type V = usize;
+    fn identity() -> Self {
+        PathMaxEdge {
+            max: W::zero(),
+            edge: None,
+        }
+    }
+    fn compress(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        if a.edge.is_none() || (b.edge.is_some() && b.max > a.max) { out.clone_from(b) }
+        else { out.clone_from(a) }
+    }
+    fn rake(a: &Self, _: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {}
+}
+
+fn brute_max(adj: &[Vec<(usize, usize)>], s: usize, t: usize) -> Option<(usize, usize, usize)> {
+    fn dfs(adj: &[Vec<(usize, usize)>], v: usize, p: usize, t: usize) -> Option<Option<(usize, usize, usize)>> {
+        if v == t { return Some(None) }
+        for &(u, w) in adj[v].iter() {
+            if u == p { continue }
+            if let Some(best) = dfs(adj, u, v, t) {
+                return Some(match best {
+                    Some((bw, _, _)) if bw >= w => best,
+                    _ => Some((w, v, u)),
+                });
+            }
+        }
+        None
+    }
+    dfs(adj, s, usize::MAX, t).unwrap()
+}
+
+pub fn path_max_edge_test() {
+    println!("path max edge");
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
+    let mut edges = vec![
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    let mut es = Vec::new();
+    for (a, b, w) in edges.iter() {
+        let w = *w;
+        es.push(link_with(v[*a], v[*b], |e| PathMaxEdge::new(w, e)));
+    }
+    let check = |edges: &[(usize, usize, usize)]| {
+        let mut adj = vec![Vec::new(); 13];
+        for (a, b, w) in edges.iter() {
+            adj[*a].push((*b, *w));
+            adj[*b].push((*a, *w));
+        }
+        for i in 0..13 {
+            for j in 0..13 {
+                if i == j || (adj[i].is_empty() && adj[j].is_empty()) { continue }
+                let expect = match brute_max(&adj, i, j) {
+                    Some(e) => e,
+                    None => continue,
+                };
+                let res = path_query(v[i], v[j]);
+                assert!(res.max == expect.0);
+                let (x, y) = unsafe { raw::edge_endpoints(res.edge.unwrap()) };
+                let w = edges.iter().find(|(a, b, _)| (v[*a] == x && v[*b] == y) || (v[*a] == y && v[*b] == x)).unwrap().2;
+                assert!(w == expect.0);
+            }
+        }
+    };
+    check(&edges);
+
+    let bottleneck = path_query(v[3], v[11]).edge.unwrap();
+    assert!(bottleneck == es[7]);
+    unsafe { raw::update_edge(bottleneck, PathMaxEdge::new(2, bottleneck)); }
+    edges[7].2 = 2;
+    check(&edges);
+    assert!(path_query(v[3], v[11]).max == 7);
+
+    let bottleneck = path_query(v[8], v[2]).edge.unwrap();
+    let (x, y) = unsafe { raw::edge_endpoints(bottleneck) };
+    cut(x, y);
+    edges.remove(1);
+    println!("cut {}-{}", x.value(), y.value());
+    assert!(path_query(v[8], v[4]).max == 7);
+}
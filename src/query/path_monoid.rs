@@ -0,0 +1,137 @@
+use crate::node::*;
+use crate::link::*;
+use crate::path_query::*;
+use crate::weight::*;
+
+pub trait Monoid: Clone {
+    fn identity() -> Self;
+    fn op(a: &Self, b: &Self) -> Self;
+}
+
+// every weight is a monoid under its sum
+impl<W: Weight> Monoid for W {
+    fn identity() -> Self {
+        W::zero()
+    }
+    fn op(a: &Self, b: &Self) -> Self {
+        a.weight_add(*b)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PathMonoid<M> {
+    pub fwd: M,
+    pub bwd: M,
+}
+
+impl<M: Monoid> PathMonoid<M> {
+    pub fn new(x: M) -> Self {
+        PathMonoid {
+            fwd: x.clone(),
+            bwd: x,
+        }
+    }
+}
+
+impl<M: Monoid> Cluster for PathMonoid<M> {
+    type V = usize;
+    fn identity() -> Self {
+        PathMonoid::new(M::identity())
+    }
+    fn compress(a: &Self, b: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) {
+        *out = PathMonoid {
+            fwd: M::op(&a.fwd, &b.fwd),
+            bwd: M::op(&b.bwd, &a.bwd),
+        };
+    }
+    fn rake(a: &Self, _: &Self, _: &usize, _: &usize, _: &usize, out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.fwd, &mut self.bwd);
+    }
+}
+
+/// The product of the edge values on the path from `v` to `u`, in that order.
+///
+/// Panics if the vertices are not connected.
+pub fn path_product<M: Monoid>(v: Vertex<PathMonoid<M>>, u: Vertex<PathMonoid<M>>) -> M {
+    path_query(v, u).fwd
+}
+
+type Mod = ModInt<998_244_353>;
+
+// x -> a * x + b
+#[derive(Clone, Debug, PartialEq)]
+struct Affine<W> {
+    a: W,
+    b: W,
+}
+
+impl<W: Weight + From<u64>> Monoid for Affine<W> {
+    fn identity() -> Self {
+        Affine { a: W::from(1), b: W::zero() }
+    }
+    fn op(f: &Self, g: &Self) -> Self {
+        Affine {
+            a: g.a.saturating_mul(f.a),
+            b: g.a.saturating_mul(f.b).weight_add(g.b),
+        }
+    }
+}
+
+pub fn path_monoid_test() {
+    println!("path monoid");
+    let v: Vec<_> = (0..13).map(Vertex::new).collect();
+    let edges = [
+        (0usize, 1usize),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (0, 5),
+        (5, 9),
+        (9, 10),
+        (10, 11),
+        (10, 12),
+        (0, 6),
+        (6, 7),
+        (7, 8),
+    ];
+    let mut par = [usize::MAX; 13];
+    let mut depth = [0; 13];
+    let mut up = vec![Affine::<Mod>::identity(); 13];
+    for (i, (a, b)) in edges.iter().enumerate() {
+        let f = Affine { a: Mod::new(2 + i as u64), b: Mod::new(7 * i as u64 + 1) };
+        link(v[*a], v[*b], PathMonoid::new(f.clone()));
+        par[*b] = *a;
+        depth[*b] = depth[*a] + 1;
+        up[*b] = f;
+    }
+    for i in 0..13 {
+        for j in 0..13 {
+            if i == j { continue }
+            let (mut x, mut y) = (i, j);
+            let mut head = Affine::identity();
+            let mut tail = Vec::new();
+            while x != y {
+                if depth[x] >= depth[y] {
+                    head = Affine::op(&head, &up[x]);
+                    x = par[x];
+                }
+                else {
+                    tail.push(up[y].clone());
+                    y = par[y];
+                }
+            }
+            let expect = tail.iter().rev().fold(head, |h, f| Affine::op(&h, f));
+            assert!(path_product(v[i], v[j]) == expect);
+        }
+    }
+    println!("3 -> 12 = {:?}", path_product(v[3], v[12]));
+
+    // a weight is the monoid of its sums
+    let w: Vec<_> = (0..4).map(Vertex::new).collect();
+    link(w[0], w[1], PathMonoid::new(5i64));
+    link(w[1], w[2], PathMonoid::new(-8i64));
+    link(w[1], w[3], PathMonoid::new(2i64));
+    assert!(path_product(w[2], w[3]) == -6);
+    assert!(path_product(w[0], w[0]) == 0);
+}
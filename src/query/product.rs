@@ -0,0 +1,72 @@
+use crate::node::*;
+use crate::link::*;
+use crate::path_query::*;
+use crate::fold::*;
+use crate::query::diameter::*;
+use crate::query::center::*;
+use crate::query::median::*;
+
+#[derive(Clone, Debug, Cluster)]
+pub struct PathStats {
+    pub length: usize,
+    pub diameter: Diameter,
+    pub center: Center,
+    pub median: Median,
+}
+
+impl PathStats {
+    pub fn new(l: usize) -> Self {
+        PathStats {
+            length: l,
+            diameter: Diameter::new(l),
+            center: Center::new(l),
+            median: Median::new(l),
+        }
+    }
+}
+
+// the `=` of `V = usize` is not a default, `Center` is
+#[derive(Clone, Debug, Cluster)]
+pub struct LengthPair<C: Cluster<V = usize> = Center>(usize, C);
+
+pub fn product_test() {
+    println!("product");
+    let v: Vec<Vertex<PathStats>> = (0..13).map(|_| Vertex::new(1)).collect();
+    let vl: Vec<Vertex<usize>> = (0..13).map(|_| Vertex::new(1)).collect();
+    let vd: Vec<Vertex<Diameter>> = (0..13).map(|_| Vertex::new(1)).collect();
+    let vm: Vec<Vertex<Median>> = (0..13).map(|_| Vertex::new(1)).collect();
+    let vp: Vec<Vertex<LengthPair>> = (0..13).map(|_| Vertex::new(1)).collect();
+    let edges = [
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], PathStats::new(*w));
+        link(vl[*a], vl[*b], *w);
+        link(vd[*a], vd[*b], Diameter::new(*w));
+        link(vm[*a], vm[*b], Median::new(*w));
+        link(vp[*a], vp[*b], LengthPair(*w, Center::new(*w)));
+    }
+    for i in 0..13 {
+        let fold = component_fold(v[i]);
+        assert!(fold.diameter.diam == component_fold(vd[i]).diam);
+        assert!(fold.center.max_dist_left == component_fold(vd[i]).max_dist_left);
+        assert!(fold.median.left_sum == component_fold(vm[i]).left_sum);
+        for j in 0..13 {
+            if i == j { continue }
+            assert!(path_query(v[i], v[j]).length == path_query(vl[i], vl[j]));
+            assert!(path_query(vp[i], vp[j]).0 == path_query(vl[i], vl[j]));
+        }
+    }
+    println!("diameter = {}", component_fold(v[0]).diameter.diam);
+}
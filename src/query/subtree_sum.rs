@@ -0,0 +1,74 @@
+use crate::node::*;
+use crate::link::*;
+use crate::fold::*;
+use crate::path_query::*;
+use crate::weight::*;
+
+#[derive(Clone, Debug)]
+pub struct SubtreeSum<W = usize> {
+    pub sum: W,
+    pub length: W,
+}
+
+impl<W: Weight> SubtreeSum<W> {
+    pub fn new(l: W) -> Self {
+        SubtreeSum { sum: W::zero(), length: l }
+    }
+}
+
+impl<W: Weight> Cluster for SubtreeSum<W> {
+    type V = W;
+    fn identity() -> Self {
+        SubtreeSum { sum: W::zero(), length: W::zero() }
+    }
+    fn compress(a: &Self, b: &Self, _: &W, _: &W, c: &W, out: &mut Self) {
+        *out = SubtreeSum {
+            sum: a.sum.weight_add(b.sum).weight_add(*c),
+            length: a.length.weight_add(b.length),
+        };
+    }
+    fn rake(a: &Self, b: &Self, _: &W, bv: &W, _: &W, out: &mut Self) {
+        *out = SubtreeSum {
+            sum: a.sum.weight_add(b.sum).weight_add(*bv),
+            length: a.length,
+        };
+    }
+    fn reverse(&mut self) {}
+}
+
+pub fn subtree_sum_test() {
+    println!("subtree sum");
+    let val = [5usize, 3, 8, 1, 4, 9, 2, 7, 6, 10, 5, 3, 8];
+    let v: Vec<_> = val.iter().map(|x| Vertex::new(*x)).collect();
+    let edges = [
+        (0usize, 1usize, 1usize),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+    let mut adj = vec![Vec::new(); 13];
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], SubtreeSum::new(*w));
+        adj[*a].push(*b);
+        adj[*b].push(*a);
+    }
+    fn side(adj: &[Vec<usize>], v: usize, p: usize, val: &[usize]) -> usize {
+        adj[v].iter().filter(|u| **u != p).map(|u| val[*u] + side(adj, *u, v, val)).sum()
+    }
+    for (a, b, w) in edges.iter() {
+        for &(x, y) in [(*a, *b), (*b, *a)].iter() {
+            let fold = subtree_fold(v[x], v[y]);
+            assert!(fold.sum == side(&adj, x, y, &val));
+            assert!(path_query(v[x], v[y]).length == *w);
+        }
+    }
+    println!("subtree of 10 under 9 = {}", val[10] + subtree_fold(v[10], v[9]).sum);
+}
@@ -0,0 +1,165 @@
+use crate::node::*;
+use crate::link::*;
+use crate::path_query::*;
+use crate::update::*;
+use crate::weight::*;
+
+const BASE: u64 = 1_000_003;
+
+// the sum and a polynomial hash of the vertex values along a path; the hash
+// reads the values as `u64`s
+#[derive(Clone, Debug)]
+pub struct VertexPath<W = u64> {
+    pub sum: W,
+    pub hash: u64,
+    pub rev_hash: u64,
+    pub pow: u64,
+}
+
+impl<W: Weight + Into<u64>> Cluster for VertexPath<W> {
+    type V = W;
+    fn identity() -> Self {
+        VertexPath {
+            sum: W::zero(),
+            hash: 0,
+            rev_hash: 0,
+            pow: 1,
+        }
+    }
+    fn compress(a: &Self, b: &Self, _: &W, _: &W, c: &W, out: &mut Self) {
+        let x: u64 = (*c).into();
+        *out = VertexPath {
+            sum: a.sum.weight_add(b.sum).weight_add(*c),
+            hash: (a.hash.wrapping_mul(BASE).wrapping_add(x)).wrapping_mul(b.pow).wrapping_add(b.hash),
+            rev_hash: (b.rev_hash.wrapping_mul(BASE).wrapping_add(x)).wrapping_mul(a.pow).wrapping_add(a.rev_hash),
+            pow: a.pow.wrapping_mul(BASE).wrapping_mul(b.pow),
+        };
+    }
+    fn rake(a: &Self, _: &Self, _: &W, _: &W, _: &W, out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {
+        std::mem::swap(&mut self.hash, &mut self.rev_hash);
+    }
+}
+
+// vertex values need not be `Copy`
+#[derive(Clone, Debug)]
+pub struct PathLabels<L = String> {
+    pub labels: Vec<L>,
+}
+
+impl<L: Clone + Default + std::fmt::Debug> Cluster for PathLabels<L> {
+    type V = L;
+    fn identity() -> Self {
+        PathLabels { labels: Vec::new() }
+    }
+    fn compress(a: &Self, b: &Self, _: &L, _: &L, c: &L, out: &mut Self) {
+        out.labels.clone_from(&a.labels);
+        out.labels.push(c.clone());
+        out.labels.extend_from_slice(&b.labels);
+    }
+    fn rake(a: &Self, _: &Self, _: &L, _: &L, _: &L, out: &mut Self) { out.clone_from(a) }
+    fn reverse(&mut self) {
+        self.labels.reverse();
+    }
+}
+
+pub fn vertex_path_test() {
+    println!("vertex path");
+    let mut val = vec![5u64, 3, 8, 1, 4, 9, 2, 7, 6, 10, 5, 3, 8];
+    let v: Vec<_> = val.iter().map(|x| Vertex::new(*x)).collect();
+    let edges = [
+        (0usize, 1usize),
+        (1, 2),
+        (1, 3),
+        (1, 4),
+        (0, 5),
+        (5, 9),
+        (9, 10),
+        (10, 11),
+        (10, 12),
+        (0, 6),
+        (6, 7),
+        (7, 8),
+    ];
+    for (a, b) in edges.iter() {
+        link(v[*a], v[*b], VertexPath::identity());
+    }
+    let mut par = [usize::MAX; 13];
+    let mut depth = [0; 13];
+    for (a, b) in edges.iter() {
+        par[*b] = *a;
+        depth[*b] = depth[*a] + 1;
+    }
+    let check = |val: &[u64]| {
+        for i in 0..13 {
+            for j in 0..13 {
+                let (mut x, mut y) = (i, j);
+                let mut head = Vec::new();
+                let mut tail = Vec::new();
+                while x != y {
+                    if depth[x] >= depth[y] {
+                        head.push(x);
+                        x = par[x];
+                    }
+                    else {
+                        tail.push(y);
+                        y = par[y];
+                    }
+                }
+                head.push(x);
+                head.extend(tail.iter().rev());
+                let seq: Vec<u64> = head.iter().map(|k| val[*k]).collect();
+                let hash = seq.iter().fold(0u64, |h, x| h.wrapping_mul(BASE).wrapping_add(*x));
+                let res = path_query_inclusive(v[i], v[j]);
+                assert!(res.sum == seq.iter().sum::<u64>());
+                assert!(res.hash == hash);
+            }
+        }
+    };
+    check(&val);
+    for (i, x) in [(1usize, 11u64), (0, 0), (10, 7), (12, 2)].iter() {
+        val[*i] = *x;
+        update_vertex(v[*i], *x);
+        check(&val);
+    }
+    println!("3 -> 12 sum = {}", path_query_inclusive(v[3], v[12]).sum);
+}
+
+pub fn vertex_label_test() {
+    println!("vertex labels");
+    let mut names: Vec<String> = (0..8).map(|i| format!("v{}", i)).collect();
+    let v: Vec<_> = names.iter().map(|x| Vertex::new(x.clone())).collect();
+    let par = [usize::MAX, 0, 0, 1, 1, 2, 5, 5];
+    let depth = [0, 1, 1, 2, 2, 2, 3, 3];
+    for i in 1..8 {
+        link(v[par[i]], v[i], PathLabels::identity());
+    }
+    let check = |names: &[String]| {
+        for i in 0..8 {
+            for j in 0..8 {
+                let (mut x, mut y) = (i, j);
+                let mut head = Vec::new();
+                let mut tail = Vec::new();
+                while x != y {
+                    if depth[x] >= depth[y] {
+                        head.push(names[x].clone());
+                        x = par[x];
+                    }
+                    else {
+                        tail.push(names[y].clone());
+                        y = par[y];
+                    }
+                }
+                head.push(names[x].clone());
+                head.extend(tail.into_iter().rev());
+                assert!(path_query_inclusive(v[i], v[j]).labels == head);
+            }
+        }
+    };
+    check(&names);
+    names[5] = "five".to_string();
+    update_vertex(v[5], names[5].clone());
+    check(&names);
+    assert!(v[5].value() == names[5]);
+    println!("3 -> 7 = {:?}", path_query_inclusive(v[3], v[7]).labels);
+}
@@ -0,0 +1,66 @@
+//! Unchecked operations and access through edge handles.
+//!
+//! The functions in the other modules check their preconditions and panic
+//! instead of corrupting the tree. The ones here skip those checks, which
+//! costs an extra `expose` or two, or work on the `NonNull<Edge<T>>` handles
+//! returned by `link`, which dangle once the edge is cut.
+
+use std::ptr::NonNull;
+use crate::node::*;
+use crate::link::link_raw;
+use crate::cut::cut_raw;
+use crate::update::update_edge_raw;
+
+/// `link` without the connectivity check.
+///
+/// # Safety
+///
+/// `v` and `u` must be in different trees.
+pub unsafe fn link<T: Cluster>(v: Vertex<T>, u: Vertex<T>, weight: T) -> NonNull<Edge<T>> {
+    link_raw(v, u, |_| weight)
+}
+
+/// `link_with` without the connectivity check.
+///
+/// # Safety
+///
+/// `v` and `u` must be in different trees.
+pub unsafe fn link_with<T: Cluster, F: FnOnce(NonNull<Edge<T>>) -> T>(v: Vertex<T>, u: Vertex<T>, f: F) -> NonNull<Edge<T>> {
+    link_raw(v, u, f)
+}
+
+/// `cut` without the adjacency check.
+///
+/// # Safety
+///
+/// There must be an edge between `v` and `u`.
+pub unsafe fn cut<T: Cluster>(v: Vertex<T>, u: Vertex<T>) -> (T, T, T) {
+    cut_raw(v, u)
+}
+
+/// Replaces the weight of the edge `e`, in the orientation it was linked with.
+///
+/// # Safety
+///
+/// `e` must have been returned by `link` or `find_edge` and not been cut since.
+pub unsafe fn update_edge<T: Cluster>(e: NonNull<Edge<T>>, weight: T) {
+    update_edge_raw(e, weight)
+}
+
+/// The endpoints of `e` in no particular order.
+///
+/// # Safety
+///
+/// `e` must have been returned by `link` or `find_edge` and not been cut since.
+pub unsafe fn edge_endpoints<T: Cluster>(e: NonNull<Edge<T>>) -> (Vertex<T>, Vertex<T>) {
+    (e.as_ref().endpoints(0), e.as_ref().endpoints(1))
+}
+
+/// The weight of `e` in the orientation it was linked with.
+///
+/// # Safety
+///
+/// `e` must have been returned by `link` or `find_edge` and not been cut since.
+pub unsafe fn edge_weight<T: Cluster>(e: NonNull<Edge<T>>) -> T {
+    e.as_ref().weight().clone()
+}
@@ -1,8 +1,38 @@
+use std::cell::Cell;
 use crate::node::*;
 use crate::expose::*;
+use crate::path_query::find_edge;
 
-fn select_rake<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(mut rake: RakeNode<T>, sel: F, right: &mut (T, T::V, T::V)) -> CompNode<T> {
+// a fold together with its two end vertices
+type Context<T> = (T, Vertex<T>, Vertex<T>);
+
+thread_local! {
+    // set while a descent may call `sel`, which must not restructure any tree
+    static SELECTING: Cell<bool> = const { Cell::new(false) };
+}
+
+// clears `SELECTING` when the descent ends, even if `sel` panics
+struct Selecting;
+
+impl Drop for Selecting {
+    fn drop(&mut self) {
+        SELECTING.with(|s| s.set(false));
+    }
+}
+
+fn selecting() -> Selecting {
+    SELECTING.with(|s| s.set(true));
+    Selecting
+}
+
+// called by `expose_raw` and `update_edge_raw`, which every operation on a tree goes through
+pub(crate) fn assert_not_selecting() {
+    assert!(!SELECTING.with(|s| s.get()), "the trees cannot be used from inside the `sel` of `select`");
+}
+
+fn select_rake<T: Cluster, F: Fn(&T, &T, &T::V, &T::V, &T::V) -> usize>(mut rake: RakeNode<T>, sel: F, right: &mut Context<T>) -> CompNode<T> {
     unsafe {
+        let mut rf = T::identity();
         rake.push();
         while let RakeNode::Node(r) = rake {
 
@@ -10,11 +40,17 @@ fn select_rake<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(mut rake: Rak
             r.as_ref().child(0).fix();
             r.as_ref().child(1).push();
             r.as_ref().child(1).fix();
-            let (rf, r0, _r1) = (T::rake(r.as_ref().child(1).fold(), right.0.clone(), r.as_ref().child(1).endpoints(0).value(), right.1, r.as_ref().child(1).endpoints(1).value()), r.as_ref().child(1).endpoints(0).value(), r.as_ref().child(1).endpoints(1).value());
-            let dir = sel(r.as_ref().child(0).fold(), rf, 
-                          r.as_ref().child(0).endpoints(0).value(), r0, r.as_ref().child(0).endpoints(1).value());
+            let (c0, c1) = (r.as_ref().child(0), r.as_ref().child(1));
+            T::rake(c1.fold(), &right.0, c1.endpoints(0).value_ref(), right.1.value_ref(), c1.endpoints(1).value_ref(), &mut rf);
+            rf.reverse();
+            let dir = sel(c0.fold(), &rf,
+                          c0.endpoints(0).value_ref(), c1.endpoints(0).value_ref(), c0.endpoints(1).value_ref());
             rake = r.as_ref().child(dir);
-            *right = (T::rake(r.as_ref().child(1 - dir).fold(), right.0.clone(), r.as_ref().child(1 - dir).endpoints(0).value(), right.1, r.as_ref().child(1 - dir).endpoints(1).value()), r.as_ref().child(1 - dir).endpoints(0).value(), r.as_ref().child(1 - dir).endpoints(1).value());
+            let other = r.as_ref().child(1 - dir);
+            // `rf` is free again, rake into it and swap it in
+            T::rake(other.fold(), &right.0, other.endpoints(0).value_ref(), right.1.value_ref(), other.endpoints(1).value_ref(), &mut rf);
+            std::mem::swap(&mut right.0, &mut rf);
+            (right.1, right.2) = (other.endpoints(0), other.endpoints(1));
             rake.push();
         }
         if let RakeNode::Leaf(comp) = rake { comp }
@@ -22,10 +58,83 @@ fn select_rake<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(mut rake: Rak
     }
 }
 
-pub fn select<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(v : Vertex<T>, sel: F) -> (Vertex<T>, Vertex<T>) {
-    let mut node = expose(v);
-    let mut left = None;
-    let mut right = None;
+// moves `fold` into `ctx` and leaves the old buffer of `ctx` in `fold` for reuse
+fn put<T: Cluster>(ctx: &mut Option<Context<T>>, fold: &mut T, x: Vertex<T>, y: Vertex<T>) {
+    match ctx {
+        Some(c) => {
+            std::mem::swap(&mut c.0, fold);
+            (c.1, c.2) = (x, y);
+        }
+        None => *ctx = Some((std::mem::replace(fold, T::identity()), x, y)),
+    }
+}
+
+/// Descends from the root of the tree containing `v` to a single edge.
+/// At each step `sel(left, right, a, b, c)` gets the folds of the two halves
+/// of the current cluster, its end values `a`, `b` and the shared middle
+/// value `c`, and returns `0` or `1` to pick a half. Returns the endpoints of
+/// the edge reached; the far one may be a dummy vertex.
+///
+/// `left` is oriented from `a` to `c` and `right` from `c` to `b`, so the
+/// split vertex `c` is at `endpoints(1)` of `left` and `endpoints(0)` of
+/// `right`. This holds when `right` is a subtree raked onto `c` as well: its
+/// fold is reversed before the call, so `right.max_dist_left` is measured
+/// from `c`. Folds of what lies outside the current cluster are already
+/// compressed or raked into the two halves.
+///
+/// `sel` must only look at its arguments: it runs in the middle of the
+/// descent, so calling any function that reads or changes a tree from it,
+/// such as `link`, `cut`, `path_query` or another `select`, panics.
+pub fn select<T: Cluster, F: Fn(&T, &T, &T::V, &T::V, &T::V) -> usize>(v : Vertex<T>, sel: F) -> (Vertex<T>, Vertex<T>) {
+    stat_op!(Select);
+    let root = expose(v);
+    let _busy = selecting();
+    select_from(root, None, None, sel)
+}
+
+/// Like `select`, but only descends into the part of the tree on `v`'s side
+/// of the edge `v`-`p`, as if the rest of the tree were not there. `sel` sees
+/// the halves in the same orientation: `left` from `a` to `c`, `right` from
+/// `c` to `b`. The same restriction on `sel` applies.
+///
+/// Panics if `v` and `p` are not adjacent.
+pub fn select_in_subtree<T: Cluster, F: Fn(&T, &T, &T::V, &T::V, &T::V) -> usize>(v: Vertex<T>, p: Vertex<T>, sel: F) -> (Vertex<T>, Vertex<T>) {
+    stat_op!(Select);
+    assert!(find_edge(v, p).is_some(), "select_in_subtree: the vertices are not adjacent");
+    unsafe {
+        soft_expose(v, p);
+        let _busy = selecting();
+        let mut root = v.handle().unwrap();
+        root.push();
+        let n = if let CompNode::Node(n) = root { n } else { unreachable!() };
+        // the subtree is the left child `a` ending at `v` with the rake hanging from `v`
+        let mut a = n.as_ref().child(0);
+        a.push();
+        a.fix();
+        let rr = match n.as_ref().rake() {
+            None => return select_from(a, None, None, sel),
+            Some(mut r) => { r.push(); r }
+        };
+        let (af, a0, a1) = (a.fold(), a.endpoints(0), a.endpoints(1));
+        let (mut rf, r0, r1) = (rr.fold().clone(), rr.endpoints(0), rr.endpoints(1));
+        rf.reverse();
+        if sel(af, &rf, a0.value_ref(), r0.value_ref(), a1.value_ref()) == 0 {
+            select_from(a, None, Some((rf, r1, r0)), sel)
+        }
+        else {
+            let mut right = (af.clone(), a0, a1);
+            let node = select_rake(rr, &sel, &mut right);
+            let (mut rf, r0, r1) = right;
+            rf.reverse();
+            select_from(node, None, Some((rf, r1, r0)), sel)
+        }
+    }
+}
+
+// descends from `node` with the folds of what lies to its left and right
+fn select_from<T: Cluster, F: Fn(&T, &T, &T::V, &T::V, &T::V) -> usize>(mut node: CompNode<T>, mut left: Option<Context<T>>, mut right: Option<Context<T>>, sel: F) -> (Vertex<T>, Vertex<T>) {
+    // scratch folds, reused at every step so the descent does not allocate
+    let (mut lf, mut rf, mut arf, mut rbf) = (T::identity(), T::identity(), T::identity(), T::identity());
     unsafe {
         node.push();
         while let CompNode::Node(n) = node {
@@ -38,58 +147,67 @@ pub fn select<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(v : Vertex<T>,
             let b = n.as_ref().child(1);
             let r = n.as_ref().rake();
 
-            let (af, a0, a1) = match left.clone() {
-                Some((lf, l0, l1)) => (T::compress(lf, a.fold(), l0, a.endpoints(1).value(), l1), l0, a.endpoints(1).value()),
-                None => (a.fold(), a.endpoints(0).value(), a.endpoints(1).value()),
+            let (af, a0, a1) = match &left {
+                Some((l, l0, l1)) => {
+                    T::compress(l, a.fold(), l0.value_ref(), a.endpoints(1).value_ref(), l1.value_ref(), &mut lf);
+                    (&lf, *l0, a.endpoints(1))
+                }
+                None => (a.fold(), a.endpoints(0), a.endpoints(1)),
             };
-            
-            let (bf, b0, b1) = match right.clone() {
-                Some((rf, _r0, r1)) => (T::compress(b.fold(), rf, b.endpoints(0).value(), r1, b.endpoints(1).value()), b.endpoints(0).value(), r1),
-                None => (b.fold(), b.endpoints(0).value(), b.endpoints(1).value()),
+            let (bf, b0, b1) = match &right {
+                Some((r, _r0, r1)) => {
+                    T::compress(b.fold(), r, b.endpoints(0).value_ref(), r1.value_ref(), b.endpoints(1).value_ref(), &mut rf);
+                    (&rf, b.endpoints(0), *r1)
+                }
+                None => (b.fold(), b.endpoints(0), b.endpoints(1)),
             };
-            let dir = sel(
-                match r {
-                    Some(r) => T::rake(af.clone(), r.fold(), a0, r.endpoints(0).value(), a1),
-                    None => af.clone(),
-                },
-                bf.clone(),
-                a0, b1, a1
-            );
+            // `af` with the rake hanging from its right end
+            let arfr = match r {
+                Some(r) => {
+                    T::rake(af, r.fold(), a0.value_ref(), r.endpoints(0).value_ref(), a1.value_ref(), &mut arf);
+                    &arf
+                }
+                None => af,
+            };
+            let dir = sel(arfr, bf, a0.value_ref(), b1.value_ref(), a1.value_ref());
             node = if dir == 0 {
-                if let Some(_) = r {
-                    let mut rbf = bf.clone();
+                if let Some(rr) = r {
+                    rbf.clone_from(bf);
                     rbf.reverse();
                     let rb0 = b1;
-                    let _rb1 = b0;
-                    let (mut rf, r0, r1) = (T::rake(r.unwrap().fold(), rbf.clone(), r.unwrap().endpoints(0).value(), rb0, r.unwrap().endpoints(1).value()), r.unwrap().endpoints(0).value(), r.unwrap().endpoints(1).value());
-                    let dir = sel(af.clone(), rf.clone(), a0, r0, a1);
+                    let (r0, r1) = (rr.endpoints(0), rr.endpoints(1));
+                    T::rake(rr.fold(), &rbf, r0.value_ref(), rb0.value_ref(), r1.value_ref(), &mut rf);
+                    rf.reverse();
+                    let dir = sel(af, &rf, a0.value_ref(), r0.value_ref(), a1.value_ref());
                     if dir == 0 {
-                        rf.reverse();
-                        right = Some((rf, r1, r0));
+                        put(&mut right, &mut rf, r1, r0);
                         n.as_ref().child(0)
                     }
                     else {
+                        T::rake(af, &rbf, a0.value_ref(), rb0.value_ref(), a1.value_ref(), &mut arf);
+                        put(&mut right, &mut arf, a0, a1);
+                        let ctx = right.as_mut().unwrap();
+                        let res = select_rake(rr, &sel, ctx);
+                        ctx.0.reverse();
+                        (ctx.1, ctx.2) = (ctx.2, ctx.1);
                         left = None;
-                        right = Some((T::rake(af, rbf, a0, rb0, a1), a0, a1));
-                        let res = select_rake(n.as_ref().rake().unwrap(), &sel, right.as_mut().unwrap());
-                        right = if let Some((mut rf, r0, r1)) = right.take() {
-                            rf.reverse();
-                            Some((rf, r1, r0))
-                        }
-                        else { None };
                         res
                     }
                 }
                 else {
-                    right = Some((bf, b0, b1));
+                    if right.is_none() { rf.clone_from(b.fold()); }
+                    put(&mut right, &mut rf, b0, b1);
                     n.as_ref().child(0)
                 }
             }
             else {
-                left = match r {
-                    Some(r) => Some((T::rake(af.clone(), r.fold(), a0, r.endpoints(0).value(), a1), a0, a1)),
-                    None => Some((af, a0, a1)),
-                };
+                if r.is_some() {
+                    put(&mut left, &mut arf, a0, a1);
+                }
+                else {
+                    if left.is_none() { lf.clone_from(a.fold()); }
+                    put(&mut left, &mut lf, a0, a1);
+                }
                 n.as_ref().child(1)
             };
             node.push();
@@ -100,3 +218,29 @@ pub fn select<T: Cluster, F: Fn(T, T, T::V, T::V, T::V) -> usize>(v : Vertex<T>,
     }
     else { unreachable!() }
 }
+
+pub fn select_reentry_test() {
+    println!("select reentry");
+    let v: Vec<_> = (0..5).map(Vertex::new).collect();
+    for i in 1..5 {
+        crate::link::link(v[i - 1], v[i], i);
+    }
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let calls: [&dyn Fn(); 3] = [
+        &|| { crate::path_query::path_query(v[0], v[4]); },
+        &|| { crate::cut::cut(v[3], v[4]); },
+        &|| { select(v[1], |_, _, _, _, _| 0); },
+    ];
+    for call in calls.iter() {
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| select(v[0], |_, _, _, _, _| { call(); 0 })));
+        assert!(res.is_err());
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| select_in_subtree(v[1], v[0], |_, _, _, _, _| { call(); 0 })));
+        assert!(res.is_err());
+    }
+    std::panic::set_hook(hook);
+    // the trees are untouched and usable again
+    assert!(crate::path_query::path_query(v[0], v[4]) == 10);
+    assert!(crate::debug::validate_cluster_tree(v[0]).is_ok());
+    select(v[0], |_, _, _, _, _| 1);
+}
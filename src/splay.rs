@@ -2,7 +2,8 @@ use std::ptr::NonNull;
 use crate::node::*;
 use crate::parent_dir::*;
 
-pub fn rotate_comp<T: Cluster>(mut t: NonNull<Compress<T>>, mut x: NonNull<Compress<T>>, dir: usize) {
+pub(crate) fn rotate_comp<T: Cluster>(mut t: NonNull<Compress<T>>, mut x: NonNull<Compress<T>>, dir: usize) {
+    stat!(rotate_comp);
     unsafe {
         let y = x.as_ref().parent();
 
@@ -42,7 +43,8 @@ pub fn rotate_comp<T: Cluster>(mut t: NonNull<Compress<T>>, mut x: NonNull<Compr
     }
 }
 
-pub fn rotate_rake<T: Cluster>(mut t: NonNull<Rake<T>>, mut x: NonNull<Rake<T>>, dir: usize) {
+pub(crate) fn rotate_rake<T: Cluster>(mut t: NonNull<Rake<T>>, mut x: NonNull<Rake<T>>, dir: usize) {
+    stat!(rotate_rake);
     unsafe {
         let y = x.as_ref().parent();
 
@@ -73,10 +75,11 @@ pub fn rotate_rake<T: Cluster>(mut t: NonNull<Rake<T>>, mut x: NonNull<Rake<T>>,
     }
 }
 
-pub fn splay_comp<T: Cluster>(mut t: NonNull<Compress<T>>) {
+pub(crate) fn splay_comp<T: Cluster>(mut t: NonNull<Compress<T>>) {
     unsafe {
         t.as_mut().push();
         while let Some((_,mut q)) = parent_dir_comp(CompNode::Node(t)) {
+            stat!(splay_steps);
             if let Some((_, mut r)) = parent_dir_comp(CompNode::Node(q)) {
                 if let Some(mut rp) = r.as_ref().parent() { rp.push(); }
                 r.as_mut().push();
@@ -104,10 +107,11 @@ pub fn splay_comp<T: Cluster>(mut t: NonNull<Compress<T>>) {
     }
 }
 
-pub fn splay_rake<T: Cluster>(mut t: NonNull<Rake<T>>) {
+pub(crate) fn splay_rake<T: Cluster>(mut t: NonNull<Rake<T>>) {
     unsafe {
         t.as_mut().push();
         while let Some((_, mut q)) = parent_dir_rake(RakeNode::Node(t)) {
+            stat!(splay_steps);
             if let Some((_, mut r)) = parent_dir_rake(RakeNode::Node(q)) {
                 if let Some(mut rp) = r.as_ref().parent() { rp.push(); }
                 r.as_mut().push();
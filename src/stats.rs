@@ -0,0 +1,235 @@
+//! Operation counters, enabled with the `stats` feature.
+//! Without the feature `stat!` and `stat_op!` expand to nothing and this module is empty.
+
+// counts one event, in the totals and under the entry point running it
+#[cfg(feature = "stats")]
+macro_rules! stat {
+    ($field:ident) => {
+        crate::stats::bump(|s| {
+            s.$field += 1;
+            if let Some(op) = crate::stats::current() { s.ops[op as usize].$field += 1; }
+        })
+    };
+}
+
+#[cfg(not(feature = "stats"))]
+macro_rules! stat {
+    ($field:ident) => {};
+}
+
+// marks the rest of the block as a call of the entry point `Op::$op`, unless
+// it runs inside another one, which keeps the work
+#[cfg(feature = "stats")]
+macro_rules! stat_op {
+    ($op:ident) => { let _op = crate::stats::enter(crate::stats::Op::$op); };
+}
+
+#[cfg(not(feature = "stats"))]
+macro_rules! stat_op {
+    ($op:ident) => {};
+}
+
+#[cfg(feature = "stats")]
+pub use self::counters::*;
+
+#[cfg(feature = "stats")]
+mod counters {
+    use std::cell::Cell;
+    use std::ops::Sub;
+    use crate::node::*;
+    use crate::link::*;
+    use crate::cut::*;
+    use crate::path_query::*;
+    use crate::forest::*;
+    use crate::expose::connected;
+
+    /// The public entry points work is broken down by. Work done inside
+    /// another entry point, such as the `expose` inside `link`, belongs to the
+    /// outer one.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub enum Op {
+        Link,
+        Cut,
+        /// `connected` and `find_edge`, which only expose
+        Expose,
+        PathQuery,
+        UpdateEdge,
+        UpdateVertex,
+        Select,
+        /// `component_fold` and `subtree_fold`
+        Fold,
+    }
+
+    impl Op {
+        pub const ALL: [Op; 8] = [Op::Link, Op::Cut, Op::Expose, Op::PathQuery, Op::UpdateEdge, Op::UpdateVertex, Op::Select, Op::Fold];
+    }
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Stats {
+        pub rotate_comp: u64,
+        pub rotate_rake: u64,
+        pub splay_steps: u64,
+        pub expose_iters: u64,
+        pub fixes: u64,
+        pub allocs: u64,
+        pub frees: u64,
+        /// The same counters for each entry point, indexed by `Op`. Work
+        /// outside every entry point, like `Vertex::new`, is only in the totals.
+        pub ops: [OpStats; 8],
+    }
+
+    /// The work of one entry point, and how many times it was called.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct OpStats {
+        pub calls: u64,
+        pub rotate_comp: u64,
+        pub rotate_rake: u64,
+        pub splay_steps: u64,
+        pub expose_iters: u64,
+        pub fixes: u64,
+        pub allocs: u64,
+        pub frees: u64,
+    }
+
+    thread_local! {
+        static STATS: Cell<Stats> = Cell::new(Stats::default());
+        static CURRENT: Cell<Option<Op>> = const { Cell::new(None) };
+    }
+
+    pub(crate) fn current() -> Option<Op> {
+        CURRENT.with(|c| c.get())
+    }
+
+    // ends the outermost entry point when dropped
+    pub(crate) struct OpGuard(bool);
+
+    impl Drop for OpGuard {
+        fn drop(&mut self) {
+            if self.0 { CURRENT.with(|c| c.set(None)); }
+        }
+    }
+
+    pub(crate) fn enter(op: Op) -> OpGuard {
+        if current().is_some() { return OpGuard(false) }
+        CURRENT.with(|c| c.set(Some(op)));
+        bump(|s| s.ops[op as usize].calls += 1);
+        OpGuard(true)
+    }
+
+    pub(crate) fn bump<F: FnOnce(&mut Stats)>(f: F) {
+        STATS.with(|s| {
+            let mut st = s.get();
+            f(&mut st);
+            s.set(st);
+        });
+    }
+
+    impl Stats {
+        /// The breakdown for `op`.
+        pub fn op(&self, op: Op) -> &OpStats {
+            &self.ops[op as usize]
+        }
+        /// The counters of this thread since the last `reset`. Work done on
+        /// other threads is never included.
+        pub fn snapshot() -> Stats {
+            STATS.with(|s| s.get())
+        }
+        /// Zeroes the counters of this thread.
+        pub fn reset() {
+            STATS.with(|s| s.set(Stats::default()));
+        }
+        /// Runs `f` and returns the work it did on this thread. If `f` calls
+        /// `reset`, counters that went down read zero.
+        pub fn measure<R, F: FnOnce() -> R>(f: F) -> (R, Stats) {
+            let before = Stats::snapshot();
+            let res = f();
+            (res, Stats::snapshot() - before)
+        }
+    }
+
+    /// Counter by counter, stopping at zero, so snapshots taken across a
+    /// `reset` or subtracted in the wrong order do not overflow.
+    impl Sub for Stats {
+        type Output = Stats;
+        fn sub(self, rhs: Stats) -> Stats {
+            Stats {
+                rotate_comp: self.rotate_comp.saturating_sub(rhs.rotate_comp),
+                rotate_rake: self.rotate_rake.saturating_sub(rhs.rotate_rake),
+                splay_steps: self.splay_steps.saturating_sub(rhs.splay_steps),
+                expose_iters: self.expose_iters.saturating_sub(rhs.expose_iters),
+                fixes: self.fixes.saturating_sub(rhs.fixes),
+                allocs: self.allocs.saturating_sub(rhs.allocs),
+                frees: self.frees.saturating_sub(rhs.frees),
+                ops: {
+                    let mut ops = self.ops;
+                    for (o, r) in ops.iter_mut().zip(rhs.ops.iter()) {
+                        *o = *o - *r;
+                    }
+                    ops
+                },
+            }
+        }
+    }
+
+    impl Sub for OpStats {
+        type Output = OpStats;
+        fn sub(self, rhs: OpStats) -> OpStats {
+            OpStats {
+                calls: self.calls.saturating_sub(rhs.calls),
+                rotate_comp: self.rotate_comp.saturating_sub(rhs.rotate_comp),
+                rotate_rake: self.rotate_rake.saturating_sub(rhs.rotate_rake),
+                splay_steps: self.splay_steps.saturating_sub(rhs.splay_steps),
+                expose_iters: self.expose_iters.saturating_sub(rhs.expose_iters),
+                fixes: self.fixes.saturating_sub(rhs.fixes),
+                allocs: self.allocs.saturating_sub(rhs.allocs),
+                frees: self.frees.saturating_sub(rhs.frees),
+            }
+        }
+    }
+
+    pub fn stats_test() {
+        println!("stats");
+        Stats::reset();
+        let v: Vec<Vertex<usize>> = (0..1000).map(|_| Vertex::new(0)).collect();
+        let (_, made) = Stats::measure(|| {
+            for i in 1..1000 {
+                link(v[i - 1], v[i], 1);
+            }
+        });
+        println!("link x999: {:?}", made);
+        assert!(made.allocs > 0 && made.frees == 0);
+        // the exposes inside `link` are charged to `link`
+        assert!(made.op(Op::Link).calls == 999 && made.op(Op::Expose).calls == 0);
+        assert!(made.op(Op::Link).fixes == made.fixes && made.op(Op::Link).expose_iters == made.expose_iters);
+        let (len, query) = Stats::measure(|| path_query(v[0], v[999]));
+        println!("path_query: {:?}", query);
+        assert!(len == 999);
+        assert!(query.allocs == 0 && query.expose_iters > 0);
+        assert!(query.op(Op::PathQuery).calls == 1 && query.op(Op::PathQuery).expose_iters == query.expose_iters);
+        let (_, removed) = Stats::measure(|| cut(v[400], v[401]));
+        println!("cut: {:?}", removed);
+        assert!(removed.allocs == 0 && removed.frees > 0 && removed.expose_iters > 0);
+        assert!(removed.op(Op::Cut).calls == 1 && removed.op(Op::Cut).frees == removed.frees);
+        let (_, other) = Stats::measure(|| connected(v[0], v[999]));
+        assert!(other.op(Op::Expose).calls == 1 && Op::ALL.iter().all(|op| *op == Op::Expose || other.op(*op).calls == 0));
+        assert!(Stats::snapshot().fixes >= made.fixes + query.fixes + removed.fixes);
+        assert!(Stats::default() - made == Stats::default());
+        // every vertex, dummy and node a forest allocates is freed when it is dropped
+        let (_, owned) = Stats::measure(|| {
+            let mut f = Forest::<usize>::new();
+            assert!(f.pop_vertex() == Err(ForestError::Empty));
+            for i in 0..100 {
+                f.add_vertex(i);
+            }
+            for i in 1..100 {
+                f.link(i / 2, i, 1).unwrap();
+            }
+            f.cut(3, 7).unwrap();
+            f.pop_vertex().unwrap_err();
+        });
+        println!("forest: {:?}", owned);
+        assert!(owned.allocs > 0 && owned.allocs == owned.frees);
+        Stats::reset();
+        assert!(Stats::snapshot() == Stats::default());
+    }
+}
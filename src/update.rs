@@ -0,0 +1,102 @@
+use std::ptr::NonNull;
+use crate::node::*;
+use crate::parent_dir::*;
+use crate::expose::*;
+use crate::path_query::find_edge;
+use crate::select::assert_not_selecting;
+
+/// Replaces the weight of the edge `v`-`u`. The weight is read in the
+/// orientation the edge was linked with, whatever the order of `v` and `u`.
+///
+/// Panics if the vertices are not adjacent.
+pub fn update_edge<T: Cluster>(v: Vertex<T>, u: Vertex<T>, weight: T) {
+    stat_op!(UpdateEdge);
+    let e = find_edge(v, u).expect("update_edge: the vertices are not adjacent");
+    update_edge_raw(e, weight)
+}
+
+// `e` must be an edge currently in a tree
+pub(crate) fn update_edge_raw<T: Cluster>(mut e: NonNull<Edge<T>>, weight: T) {
+    stat_op!(UpdateEdge);
+    assert_not_selecting();
+    unsafe { e.as_mut().weight_set(weight); }
+    refresh_edge(e);
+}
+
+// refolds `e` and every cluster above it, exposing it first so the walk is short
+fn refresh_edge<T: Cluster>(mut e: NonNull<Edge<T>>) {
+    unsafe {
+        match e.as_ref().parent() {
+            Some(ParentNode::Compress(n)) if parent_dir_comp_guard(CompNode::Leaf(e)).is_some() => {
+                expose_raw(CompNode::Node(n));
+            }
+            Some(_) => {
+                expose_raw(CompNode::Leaf(e));
+            }
+            None => {}
+        }
+        e.as_mut().fix();
+        let mut par = e.as_ref().parent();
+        while let Some(mut p) = par {
+            p.fix();
+            par = p.parent();
+        }
+    }
+}
+
+// the edges incident to `v` below `root`, where `v` is the middle vertex of `root` or one of its boundaries
+fn incident_edges<T: Cluster>(root: CompNode<T>, v: Vertex<T>) -> Vec<NonNull<Edge<T>>> {
+    // rake trees can be deep, so walk with an explicit stack
+    let mut stack = vec![RakeNode::Leaf(root)];
+    let mut edges = Vec::new();
+    while let Some(node) = stack.pop() {
+        unsafe {
+            match node {
+                RakeNode::Node(r) => {
+                    stack.push(r.as_ref().child(0));
+                    stack.push(r.as_ref().child(1));
+                }
+                RakeNode::Leaf(mut comp) => {
+                    comp.push();
+                    match comp {
+                        CompNode::Leaf(e) => edges.push(e),
+                        CompNode::Node(n) => {
+                            let (left, right) = (n.as_ref().child(0), n.as_ref().child(1));
+                            if left.endpoints(1) == v {
+                                stack.push(RakeNode::Leaf(left));
+                                stack.push(RakeNode::Leaf(right));
+                                stack.extend(n.as_ref().rake());
+                            }
+                            else if left.endpoints(0) == v {
+                                stack.push(RakeNode::Leaf(left));
+                            }
+                            else {
+                                stack.push(RakeNode::Leaf(right));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Replaces the value of `v` and refreshes the folds that depend on it.
+///
+/// This is an `expose` and one refold of the root, unless `T::UPDATE_VERTEX_REBUILDS_EDGES`
+/// is set: then every edge at `v` is refreshed like in `update_edge`, which
+/// costs `O(deg(v) log n)` amortized.
+pub fn update_vertex<T: Cluster>(mut v: Vertex<T>, val: T::V) {
+    stat_op!(UpdateVertex);
+    let mut root = expose(v);
+    v.value_set(val);
+    if T::UPDATE_VERTEX_REBUILDS_EDGES {
+        for e in incident_edges(root, v) {
+            refresh_edge(e);
+        }
+    }
+    else {
+        root.fix();
+    }
+}
@@ -0,0 +1,195 @@
+//! Edge and vertex weights for the built-in clusters.
+//!
+//! `Diameter`, `Center`, `Median` and the others only need to add weights,
+//! compare them and, for `Median`, multiply a distance by a weight. `Weight`
+//! is that much. It is implemented for the primitive integers, which saturate
+//! instead of overflowing, for `f64` through `TotalF64`, and for `ModInt`.
+
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+/// A totally ordered weight with addition and multiplication. Integers
+/// saturate at their bounds, `ModInt` wraps around its modulus. The sum is
+/// `weight_add` rather than `add`, which would shadow `std::ops::Add::add`
+/// wherever both traits are in scope.
+///
+/// Integer `weight_add` saturates silently, with no panic even in debug builds. A
+/// sum stuck at the bound no longer compares correctly with the others, so a
+/// `diameter`, `center` or `weighted_median` over saturated sums is wrong
+/// without any sign of it. Pick a type wide enough for the longest path.
+pub trait Weight: Copy + Default + Ord + Debug {
+    fn zero() -> Self;
+    fn weight_add(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+}
+
+macro_rules! int_weight {
+    ($($t:ty),*) => {$(
+        impl Weight for $t {
+            fn zero() -> Self { 0 }
+            fn weight_add(self, rhs: Self) -> Self { self.saturating_add(rhs) }
+            fn saturating_mul(self, rhs: Self) -> Self { <$t>::saturating_mul(self, rhs) }
+        }
+    )*};
+}
+
+int_weight!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// An `f64` ordered by `f64::total_cmp`, so `-0.0 < 0.0` and NaNs sort to the ends.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TotalF64(pub f64);
+
+impl PartialEq for TotalF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalF64 {}
+
+impl PartialOrd for TotalF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+macro_rules! total_from {
+    ($($t:ty),*) => {$(
+        impl From<$t> for TotalF64 {
+            fn from(x: $t) -> Self { TotalF64(f64::from(x)) }
+        }
+    )*};
+}
+
+total_from!(u8, u16, u32, i8, i16, i32, f32, f64);
+
+impl Weight for TotalF64 {
+    fn zero() -> Self { TotalF64(0.0) }
+    fn weight_add(self, rhs: Self) -> Self { TotalF64(self.0 + rhs.0) }
+    fn saturating_mul(self, rhs: Self) -> Self { TotalF64(self.0 * rhs.0) }
+}
+
+/// An integer modulo `M`, which must satisfy `0 < M < 2^63` so that the sum
+/// of two representatives cannot overflow; any other `M` fails to compile once
+/// `new` or `weight_add` is used. Ordered by its representative in `0..M`, so clusters
+/// that pick a maximum or a median give meaningless answers with it; sums and
+/// products are exact.
+///
+/// ```compile_fail
+/// use toptree::weight::ModInt;
+/// let x = ModInt::<0>::new(1);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModInt<const M: u64>(u64);
+
+impl<const M: u64> ModInt<M> {
+    // evaluated when `new` or `weight_add` is instantiated, so a bad `M` is a compile error
+    const MODULUS_OK: () = assert!(M > 0 && M < 1 << 63, "ModInt needs 0 < M < 2^63");
+
+    pub fn new(x: u64) -> Self {
+        let () = Self::MODULUS_OK;
+        ModInt(x % M)
+    }
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+impl<const M: u64> From<u64> for ModInt<M> {
+    fn from(x: u64) -> Self {
+        ModInt::new(x)
+    }
+}
+
+impl<const M: u64> Weight for ModInt<M> {
+    fn zero() -> Self { ModInt(0) }
+    fn weight_add(self, rhs: Self) -> Self {
+        let () = Self::MODULUS_OK;
+        ModInt((self.0 + rhs.0) % M)
+    }
+    fn saturating_mul(self, rhs: Self) -> Self { ModInt((self.0 as u128 * rhs.0 as u128 % M as u128) as u64) }
+}
+
+pub fn weight_test() {
+    use crate::node::Vertex;
+    use crate::link::link;
+    use crate::fold::component_fold;
+    use crate::path_query::path_query;
+    use crate::query::diameter::*;
+    use crate::query::center::*;
+    use crate::query::median::*;
+    use crate::query::subtree_sum::*;
+
+    println!("weights");
+    let edges = [
+        (0usize, 1usize, 1i64),
+        (1, 2, 10),
+        (1, 3, 3),
+        (1, 4, 4),
+        (0, 5, 3),
+        (5, 9, 4),
+        (9, 10, 7),
+        (10, 11, 9),
+        (10, 12, 1),
+        (0, 6, 3),
+        (6, 7, 3),
+        (7, 8, 7),
+    ];
+
+    // negative edges; farthest distances and the folded diameter count the empty path
+    let v: Vec<Vertex<Diameter<i64>>> = (0..13).map(Vertex::new).collect();
+    let l: Vec<Vertex<i64>> = (0..13).map(Vertex::new).collect();
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Diameter::new(w - 5));
+        link(l[*a], l[*b], w - 5);
+    }
+    let far = |i: usize| (0..13).map(|j| path_query(l[i], l[j])).max().unwrap();
+    for (i, x) in v.iter().enumerate() {
+        let (f, d) = farthest(*x);
+        assert!(d == far(i) && (f == *x || path_query(*x, f).length == d));
+    }
+    let best = (0..13).map(far).max().unwrap();
+    assert!(component_fold(v[3]).diam == best);
+    for x in v.iter() {
+        let (a, b, d) = diameter(*x);
+        assert!(d == best && (a == b || path_query(a, b).length == d));
+    }
+    println!("signed diameter = {}", best);
+
+    // f64 distances
+    let v: Vec<Vertex<Center<TotalF64>>> = (0..13).map(Vertex::new).collect();
+    for (a, b, w) in edges.iter() {
+        link(v[*a], v[*b], Center::new(TotalF64(*w as f64 / 4.0)));
+    }
+    let (c, radius) = center(v[0]);
+    let min_ecc = v.iter().map(|x| component_fold(*x).max_dist_left).min().unwrap();
+    assert!(radius == min_ecc && component_fold(c).max_dist_left == min_ecc);
+    println!("f64 radius = {:?}", radius.0);
+
+    // costs that overflow u64 saturate there, and are exact in u128
+    let big = u64::MAX / 2;
+    let s: Vec<Vertex<Median<u64>>> = (0..3).map(|_| Vertex::new(big)).collect();
+    let w: Vec<Vertex<Median<u64, u128>>> = (0..3).map(|_| Vertex::new(big)).collect();
+    for i in 1..3 {
+        link(s[0], s[i], Median::new(3));
+        link(w[0], w[i], Median::new(3));
+    }
+    assert!(weighted_median(s[1]).1 == u64::MAX);
+    assert!(weighted_median(w[1]) == (w[0], 6 * big as u128));
+
+    // vertex sums modulo a prime
+    const P: u64 = 998_244_353;
+    let m: Vec<Vertex<SubtreeSum<ModInt<P>>>> = (0..13).map(|i| Vertex::new(ModInt::new(P - 1 - i))).collect();
+    for (a, b, w) in edges.iter() {
+        link(m[*a], m[*b], SubtreeSum::new(ModInt::new(*w as u64)));
+    }
+    let expect = (0..13).map(|i| P - 1 - i).sum::<u64>() % P;
+    assert!(component_fold(m[4]).sum.weight_add(ModInt::new(P - 5)) == ModInt::new(expect));
+    println!("sum mod p = {}", expect);
+}
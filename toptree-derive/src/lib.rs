@@ -0,0 +1,265 @@
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use std::iter::FromIterator;
+
+struct Field {
+    name: String,
+    ty: String,
+}
+
+// a message and the tokens it is about
+type Error = (String, Span);
+
+struct Input {
+    name: String,
+    impl_generics: String,
+    ty_generics: String,
+    where_clause: String,
+    fields: Vec<Field>,
+}
+
+fn is_punct(t: &TokenTree, c: char) -> bool {
+    match t {
+        TokenTree::Punct(p) => p.as_char() == c,
+        _ => false,
+    }
+}
+
+fn is_ident(t: &TokenTree, s: &str) -> bool {
+    match t {
+        TokenTree::Ident(id) => id.to_string() == s,
+        _ => false,
+    }
+}
+
+fn stream(tokens: &[TokenTree]) -> String {
+    TokenStream::from_iter(tokens.iter().cloned()).to_string()
+}
+
+// the nesting depth of `<...>` after each token; the `>` of `->` closes nothing
+// and `(...)`, `[...]` arrive as single groups, so they need no tracking
+fn angle_depths(tokens: &[TokenTree]) -> Vec<usize> {
+    let mut depth = 0usize;
+    let mut res = Vec::with_capacity(tokens.len());
+    for (i, t) in tokens.iter().enumerate() {
+        if is_punct(t, '<') { depth += 1; }
+        let arrow = i > 0 && is_punct(&tokens[i - 1], '-');
+        if is_punct(t, '>') && !arrow && depth > 0 { depth -= 1; }
+        res.push(depth);
+    }
+    res
+}
+
+// splits at commas outside of `<...>`
+fn split_commas(tokens: &[TokenTree]) -> Vec<Vec<TokenTree>> {
+    let mut res = vec![Vec::new()];
+    for (t, depth) in tokens.iter().zip(angle_depths(tokens)) {
+        if depth == 0 && is_punct(t, ',') {
+            res.push(Vec::new());
+        }
+        else {
+            res.last_mut().unwrap().push(t.clone());
+        }
+    }
+    res.retain(|c| !c.is_empty());
+    res
+}
+
+// skips `#[...]` attributes and `pub`, `pub(crate)` visibility
+fn skip_attrs_and_vis(tokens: &[TokenTree]) -> &[TokenTree] {
+    let mut i = 0;
+    loop {
+        if i + 1 < tokens.len() && is_punct(&tokens[i], '#') {
+            i += 2;
+        }
+        else if i < tokens.len() && is_ident(&tokens[i], "pub") {
+            i += 1;
+            if let Some(TokenTree::Group(g)) = tokens.get(i) {
+                if g.delimiter() == Delimiter::Parenthesis { i += 1; }
+            }
+        }
+        else {
+            return &tokens[i..];
+        }
+    }
+}
+
+fn parse_generics(tokens: &[TokenTree]) -> Result<(String, String), Error> {
+    let mut impl_params = Vec::new();
+    let mut ty_params = Vec::new();
+    for param in split_commas(tokens) {
+        let named = |rest: usize| -> Result<(), Error> {
+            if param.len() < rest { Err(("expected a generic parameter name".to_string(), param[0].span())) } else { Ok(()) }
+        };
+        let (name, rest) = if is_punct(&param[0], '\'') {
+            named(2)?;
+            (stream(&param[..2]), 2)
+        }
+        else if is_ident(&param[0], "const") {
+            named(2)?;
+            (param[1].to_string(), 2)
+        }
+        else {
+            (param[0].to_string(), 1)
+        };
+        // a default, but not the `=` of an associated type in a bound
+        let depths = angle_depths(&param);
+        let end = (rest..param.len()).find(|&j| depths[j] == 0 && is_punct(&param[j], '=')).unwrap_or(param.len());
+        impl_params.push(stream(&param[..end]));
+        ty_params.push(name);
+    }
+    if ty_params.is_empty() {
+        Ok((String::new(), String::new()))
+    }
+    else {
+        Ok((format!("<{}>", impl_params.join(", ")), format!("<{}>", ty_params.join(", "))))
+    }
+}
+
+fn parse(input: TokenStream) -> Result<Input, Error> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let mut i = tokens.iter().position(|t| is_ident(t, "struct") || is_ident(t, "enum") || is_ident(t, "union"))
+        .ok_or_else(|| ("expected a struct".to_string(), Span::call_site()))?;
+    if !is_ident(&tokens[i], "struct") {
+        return Err(("#[derive(Cluster)] is only supported on structs".to_string(), tokens[i].span()));
+    }
+    i += 1;
+    let name = match tokens.get(i) {
+        Some(TokenTree::Ident(id)) => id.to_string(),
+        _ => return Err(("expected the name of the struct".to_string(), tokens[i - 1].span())),
+    };
+    let name_span = tokens[i].span();
+    i += 1;
+
+    let (mut impl_generics, mut ty_generics) = (String::new(), String::new());
+    if i < tokens.len() && is_punct(&tokens[i], '<') {
+        let start = i + 1;
+        i += angle_depths(&tokens[i..]).iter().position(|d| *d == 0)
+            .ok_or_else(|| ("unclosed generics".to_string(), tokens[i].span()))?;
+        let (ig, tg) = parse_generics(&tokens[start..i])?;
+        impl_generics = ig;
+        ty_generics = tg;
+        i += 1;
+    }
+
+    let body = tokens[i..].iter().position(|t| match t {
+        TokenTree::Group(g) => g.delimiter() == Delimiter::Brace || g.delimiter() == Delimiter::Parenthesis,
+        _ => false,
+    }).map(|p| p + i).ok_or_else(|| ("unit structs cannot derive Cluster".to_string(), name_span))?;
+    let mut where_clause = stream(&tokens[i..body]);
+    let group = match &tokens[body] {
+        TokenTree::Group(g) => g.clone(),
+        _ => unreachable!(),
+    };
+    if group.delimiter() == Delimiter::Parenthesis {
+        // tuple structs put the where clause after the fields
+        let rest: Vec<TokenTree> = tokens[body + 1..].iter().filter(|t| !is_punct(t, ';')).cloned().collect();
+        where_clause = stream(&rest);
+    }
+
+    let field_tokens: Vec<TokenTree> = group.stream().into_iter().collect();
+    let mut fields = Vec::new();
+    for (idx, chunk) in split_commas(&field_tokens).iter().enumerate() {
+        let span = chunk[0].span();
+        let chunk = skip_attrs_and_vis(chunk);
+        if chunk.is_empty() {
+            return Err(("expected a field type".to_string(), span));
+        }
+        if group.delimiter() == Delimiter::Brace {
+            if chunk.len() < 3 || !is_punct(&chunk[1], ':') {
+                return Err(("unexpected field syntax".to_string(), chunk[0].span()));
+            }
+            fields.push(Field { name: chunk[0].to_string(), ty: stream(&chunk[2..]) });
+        }
+        else {
+            fields.push(Field { name: idx.to_string(), ty: stream(chunk) });
+        }
+    }
+    if fields.is_empty() {
+        return Err(("#[derive(Cluster)] needs at least one field".to_string(), group.span()));
+    }
+    Ok(Input { name, impl_generics, ty_generics, where_clause, fields })
+}
+
+fn expand(input: &Input) -> String {
+    let cluster = "::toptree::node::Cluster";
+    let build = |f: &dyn Fn(&Field) -> String| {
+        let inits: Vec<String> = input.fields.iter().map(|field| format!("{}: {}", field.name, f(field))).collect();
+        format!("{} {{ {} }}", input.name, inits.join(", "))
+    };
+    let identity = build(&|f| format!("<{} as {}>::identity()", f.ty, cluster));
+    let first = &input.fields[0].ty;
+    // the vertex values of the first field, seen as those of field `i`
+    let values = |i: usize, names: &[&str]| -> String {
+        let f = &input.fields[i];
+        names.iter().map(|x| {
+            if i == 0 { x.to_string() }
+            else { format!("::toptree::node::SameVertex::<<{} as {}>::V>::as_same({})", f.ty, cluster, x) }
+        }).collect::<Vec<_>>().join(", ")
+    };
+    let each = |op: &str| -> String {
+        input.fields.iter().enumerate().map(|(i, f)| format!("<{} as {}>::{}(&left.{n}, &right.{n}, {}, &mut out.{n});", f.ty, cluster, op, values(i, &["a", "b", "c"]), n = f.name)).collect::<Vec<_>>().join(" ")
+    };
+    let compress = each("compress");
+    let rake = each("rake");
+    let from_edge = input.fields.iter().enumerate().map(|(i, f)| format!("<{} as {}>::from_edge(&weight.{n}, {}, &mut out.{n});", f.ty, cluster, values(i, &["a", "b"]), n = f.name)).collect::<Vec<_>>().join(" ");
+    let rebuilds: Vec<String> = input.fields.iter().map(|f| format!("<{} as {}>::UPDATE_VERTEX_REBUILDS_EDGES", f.ty, cluster)).collect();
+    // every field must fold over the vertex values of the first one
+    let mut bounds: Vec<String> = input.fields[1..].iter().map(|f| format!("<{} as {c}>::V: ::toptree::node::SameVertex<<{} as {c}>::V>", first, f.ty, c = cluster)).collect();
+    let given = input.where_clause.trim().trim_start_matches("where").trim().trim_end_matches(',');
+    if !given.is_empty() {
+        bounds.insert(0, given.to_string());
+    }
+    let where_clause = if bounds.is_empty() { String::new() } else { format!("where {}", bounds.join(", ")) };
+    let reverse: Vec<String> = input.fields.iter().map(|f| format!("<{} as {}>::reverse(&mut self.{});", f.ty, cluster, f.name)).collect();
+    format!(
+        "impl{ig} {cluster} for {name}{tg} {wc} {{
+            type V = <{first} as {cluster}>::V;
+            const UPDATE_VERTEX_REBUILDS_EDGES: bool = {rebuilds};
+            fn identity() -> Self {{ {identity} }}
+            fn compress(left: &Self, right: &Self, a: &Self::V, b: &Self::V, c: &Self::V, out: &mut Self) {{ {compress} }}
+            fn rake(left: &Self, right: &Self, a: &Self::V, b: &Self::V, c: &Self::V, out: &mut Self) {{ {rake} }}
+            fn reverse(&mut self) {{ {reverse} }}
+            fn from_edge(weight: &Self, a: &Self::V, b: &Self::V, out: &mut Self) {{ {from_edge} }}
+        }}",
+        ig = input.impl_generics,
+        cluster = cluster,
+        name = input.name,
+        tg = input.ty_generics,
+        wc = where_clause,
+        first = first,
+        identity = identity,
+        compress = compress,
+        rake = rake,
+        reverse = reverse.join(" "),
+        from_edge = from_edge,
+        rebuilds = rebuilds.join(" || "),
+    )
+}
+
+// `compile_error!(msg)` reported at `span`
+fn compile_error(msg: &str, span: Span) -> TokenStream {
+    let mut lit = Literal::string(msg);
+    lit.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    let mut args = Group::new(Delimiter::Parenthesis, TokenStream::from(TokenTree::Literal(lit)));
+    args.set_span(span);
+    let mut semi = Punct::new(';', Spacing::Alone);
+    semi.set_span(span);
+    TokenStream::from_iter(vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(args),
+        TokenTree::Punct(semi),
+    ])
+}
+
+#[proc_macro_derive(Cluster)]
+pub fn derive_cluster(input: TokenStream) -> TokenStream {
+    match parse(input) {
+        Ok(input) => expand(&input).parse().unwrap_or_else(|_| {
+            compile_error("#[derive(Cluster)] could not expand this struct", Span::call_site())
+        }),
+        Err((msg, span)) => compile_error(&msg, span),
+    }
+}